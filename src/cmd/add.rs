@@ -1,7 +1,7 @@
 use clap::{Args, Parser, Subcommand};
-use maint::DataStore;
+use maint::Store;
 
-use crate::cmd::today_utc;
+use crate::cmd::{overlap, overlap_hint, today_utc, warn_outside_period};
 
 #[derive(Parser)]
 pub struct Cmd {
@@ -44,19 +44,32 @@ struct Contract {
     /// Total points for the contract
     #[arg(long)]
     pub total_points: u32,
+
+    /// Add the contract even if it overlaps another contract of the customer
+    #[arg(long)]
+    pub allow_overlap: bool,
 }
 
 #[derive(Args)]
 struct Request {
     /// ID of the contract for this request
-    pub contract_id: u32,
+    #[arg(required_unless_present = "customer")]
+    pub contract_id: Option<u32>,
+
+    /// ID of the customer for this request, to use the contract covering its date
+    #[arg(long, conflicts_with = "contract_id")]
+    pub customer: Option<u32>,
 
     /// Detailed description of the request
     #[arg(long)]
     pub description: Option<String>,
 
-    #[arg(default_value_t = today_utc())]
-    request_date: chrono::NaiveDate,
+    /// Date of the request. Defaults to today.
+    request_date: Option<chrono::NaiveDate>,
+
+    /// Same as the positional request date, for use with --customer
+    #[arg(long, conflicts_with = "request_date")]
+    date: Option<chrono::NaiveDate>,
 }
 
 #[derive(Args)]
@@ -78,21 +91,31 @@ struct Work {
 }
 
 impl Cmd {
-    pub fn run(&self, ds: &DataStore) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
         match &self.arg {
             Arg::Customer(arg) => ds.add_customer(&arg.name)?,
-            Arg::Contract(arg) => ds.add_contract(
-                arg.customer_id,
-                &arg.start_date,
-                &arg.start_date,
-                arg.total_points,
-            )?,
+            Arg::Contract(arg) => ds
+                .add_contract(
+                    arg.customer_id,
+                    &arg.start_date,
+                    &arg.end_date,
+                    arg.total_points,
+                    overlap(arg.allow_overlap),
+                )
+                .map_err(overlap_hint)?,
             Arg::Request(arg) => {
                 let description = match arg.description {
                     Some(ref description) => description,
                     None => &crate::get_editor_description("")?,
                 };
-                ds.add_request(arg.contract_id, description, &arg.request_date)?
+                let request_date = arg.request_date.or(arg.date).unwrap_or_else(today_utc);
+                let contract_id = match (arg.contract_id, arg.customer) {
+                    (Some(contract_id), _) => contract_id,
+                    (None, Some(customer_id)) => ds.active_contract(customer_id, request_date)?.id,
+                    (None, None) => unreachable!("clap requires one of them"),
+                };
+                warn_outside_period(ds, contract_id, request_date)?;
+                ds.add_request(contract_id, description, &request_date)?
             }
             Arg::Work(arg) => {
                 let description = match arg.description {
@@ -0,0 +1,122 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{Args, Parser, Subcommand};
+use maint::Store;
+
+#[derive(Args)]
+pub struct Attach {
+    /// ID of the request to attach the file to
+    pub request_id: u32,
+
+    /// File to attach
+    pub file: PathBuf,
+
+    /// ID of a work log of the request to attach the file to instead
+    #[arg(long)]
+    pub work: Option<u32>,
+}
+
+impl Attach {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let request = ds.get_request(self.request_id)?;
+        if let Some(work_id) = self.work {
+            let work = ds.get_work(work_id)?;
+            if work.request_id != request.id {
+                return Err(format!(
+                    "work {} belongs to request {}, not {}",
+                    work.id, work.request_id, request.id
+                )
+                .into());
+            }
+        }
+
+        let filename = self.file.file_name().ok_or("not a file")?.to_string_lossy();
+        let data = fs::read(&self.file)?;
+        ds.add_attachment(request.id, self.work, &filename, &data)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct Attachments {
+    /// ID of the request to list attachments for
+    pub request_id: u32,
+}
+
+impl Attachments {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let attachments = ds.list_attachments(self.request_id)?;
+        print!("{}", serde_yaml::to_string(&attachments)?);
+
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+pub struct Cmd {
+    #[command(subcommand)]
+    pub arg: Arg,
+}
+
+#[derive(Subcommand)]
+pub enum Arg {
+    /// Save an attachment to a file
+    Get(Get),
+}
+
+#[derive(Args)]
+pub struct Get {
+    /// ID of the attachment
+    pub id: u32,
+
+    /// Where to write the file. Defaults to its original name.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Overwrite the file if it already exists
+    #[arg(long)]
+    pub force: bool,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.arg {
+            Arg::Get(arg) => {
+                let attachment = ds.get_attachment(arg.id)?;
+                let output = match arg.output {
+                    Some(ref output) => output.clone(),
+                    // The stored name may come from someone else; only its
+                    // last component decides where the file goes.
+                    None => Path::new(&attachment.filename)
+                        .file_name()
+                        .map(PathBuf::from)
+                        .ok_or_else(|| {
+                            format!(
+                                "attachment {} has no usable file name ({:?}); use --output",
+                                attachment.id, attachment.filename
+                            )
+                        })?,
+                };
+                let data = ds.attachment_data(attachment.id)?;
+                let file = if arg.force {
+                    fs::File::create(&output)
+                } else {
+                    fs::File::create_new(&output)
+                };
+                let mut file = file.map_err(|e| match e.kind() {
+                    std::io::ErrorKind::AlreadyExists => format!(
+                        "{} already exists; use --force to overwrite it",
+                        output.display()
+                    ),
+                    _ => format!("{}: {}", output.display(), e),
+                })?;
+                file.write_all(&data)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use chrono::{Days, NaiveDate};
+use maint::ContractUsage;
+
+const WIDTH: usize = 60;
+const HEIGHT: usize = 16;
+
+const ACTUAL: char = '█';
+const IDEAL: char = '·';
+
+/// Burn-down of the points left on a contract up to `as_of`, drawn against the
+/// ideal linear burn from all points at the start to none at the end.
+pub fn burn_down(usage: &ContractUsage, as_of: NaiveDate) -> String {
+    let remaining_by_date = usage.remaining_by_date();
+    let total = usage.total_points as i64;
+    let days = (usage.end_date - usage.start_date).num_days().max(0) as u64;
+
+    let remaining = |date: NaiveDate| {
+        remaining_by_date
+            .iter()
+            .take_while(|(d, _)| *d <= date)
+            .last()
+            .map_or(total, |(_, r)| *r)
+    };
+    let ideal = |date: NaiveDate| {
+        if days == 0 {
+            0.0
+        } else {
+            let elapsed = (date - usage.start_date).num_days() as f64;
+            total as f64 * (1.0 - elapsed / days as f64)
+        }
+    };
+
+    let top = total.max(1) as f64;
+    let bottom = remaining_by_date
+        .iter()
+        .map(|(_, r)| *r)
+        .min()
+        .unwrap_or(0)
+        .min(0) as f64;
+    let row = |value: f64| {
+        let scaled = (value - bottom) / (top - bottom) * (HEIGHT - 1) as f64;
+        (HEIGHT - 1) - scaled.round().clamp(0.0, (HEIGHT - 1) as f64) as usize
+    };
+
+    let mut grid = vec![vec![' '; WIDTH]; HEIGHT];
+    let dates =
+        (0..WIDTH).map(|col| usage.start_date + Days::new(days * col as u64 / (WIDTH - 1) as u64));
+    for (col, date) in dates.enumerate() {
+        grid[row(ideal(date))][col] = IDEAL;
+        if date <= as_of {
+            grid[row(remaining(date) as f64)][col] = ACTUAL;
+        }
+    }
+
+    let label_width = total.to_string().len().max(bottom.to_string().len());
+    let mut out = String::new();
+    for (i, line) in grid.iter().enumerate() {
+        let label = if i == 0 {
+            total.to_string()
+        } else if i == HEIGHT - 1 {
+            bottom.to_string()
+        } else {
+            String::new()
+        };
+        let line: String = line.iter().collect();
+        out.push_str(&format!("{:>label_width$} ┤{}\n", label, line.trim_end()));
+    }
+    out.push_str(&format!("{:>label_width$} └{}\n", "", "─".repeat(WIDTH)));
+    let start = usage.start_date.to_string();
+    let end = usage.end_date.to_string();
+    out.push_str(&format!(
+        "{:>label_width$}  {}{}{}\n",
+        "",
+        start,
+        " ".repeat(WIDTH.saturating_sub(start.len() + end.len())),
+        end
+    ));
+    out.push_str(&format!(
+        "{} points left as of {} ({} of {})   {} ideal\n",
+        ACTUAL,
+        as_of,
+        remaining(as_of),
+        total,
+        IDEAL
+    ));
+    out
+}
+
+/// The same burn-down as a standalone SVG image.
+pub fn burn_down_svg(usage: &ContractUsage, as_of: NaiveDate) -> String {
+    const W: f64 = 640.0;
+    const H: f64 = 320.0;
+    const LEFT: f64 = 48.0;
+    const RIGHT: f64 = 16.0;
+    const TOP: f64 = 16.0;
+    const BOTTOM: f64 = 40.0;
+
+    let remaining_by_date = usage.remaining_by_date();
+    let total = usage.total_points as i64;
+    let days = (usage.end_date - usage.start_date).num_days().max(1) as f64;
+    let top = total.max(1);
+    let bottom = remaining_by_date
+        .iter()
+        .map(|(_, r)| *r)
+        .min()
+        .unwrap_or(0)
+        .min(0);
+
+    let x = |date: NaiveDate| {
+        let elapsed = ((date - usage.start_date).num_days() as f64).clamp(0.0, days);
+        LEFT + elapsed / days * (W - LEFT - RIGHT)
+    };
+    let y = |value: i64| TOP + (top - value) as f64 / (top - bottom) as f64 * (H - TOP - BOTTOM);
+
+    let mut actual = vec![(x(usage.start_date), y(total))];
+    let mut last = total;
+    for (date, remaining) in remaining_by_date.iter().filter(|(d, _)| *d <= as_of) {
+        actual.push((x(*date), y(last)));
+        actual.push((x(*date), y(*remaining)));
+        last = *remaining;
+    }
+    if as_of >= usage.start_date {
+        actual.push((x(as_of.min(usage.end_date)), y(last)));
+    }
+    let actual = actual
+        .iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let (x0, x1) = (x(usage.start_date), x(usage.end_date));
+    let (y_top, y_zero, y_bottom) = (y(top), y(0), y(bottom));
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{W}" height="{H}" viewBox="0 0 {W} {H}" font-family="sans-serif" font-size="12">
+  <line x1="{x0:.1}" y1="{y_top:.1}" x2="{x0:.1}" y2="{y_bottom:.1}" stroke="#888"/>
+  <line x1="{x0:.1}" y1="{y_zero:.1}" x2="{x1:.1}" y2="{y_zero:.1}" stroke="#888"/>
+  <text x="{label_x:.1}" y="{y_top:.1}" text-anchor="end" dominant-baseline="middle">{top}</text>
+  <text x="{label_x:.1}" y="{y_zero:.1}" text-anchor="end" dominant-baseline="middle">0</text>
+  <text x="{x0:.1}" y="{date_y:.1}">{start}</text>
+  <text x="{x1:.1}" y="{date_y:.1}" text-anchor="end">{end}</text>
+  <line x1="{x0:.1}" y1="{y_total:.1}" x2="{x1:.1}" y2="{y_zero:.1}" stroke="#999" stroke-dasharray="4 4"/>
+  <polyline points="{actual}" fill="none" stroke="#1f6feb" stroke-width="2"/>
+</svg>
+"##,
+        label_x = LEFT - 6.0,
+        date_y = H - BOTTOM + 18.0,
+        y_total = y(total),
+        start = usage.start_date,
+        end = usage.end_date,
+    )
+}
@@ -0,0 +1,40 @@
+use std::{env, path::PathBuf};
+
+use serde::Deserialize;
+
+/// Settings read from `MAINT_CONFIG`, or `~/.maint.yaml` if that is not set.
+/// A missing file means the defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Shown in the header of exported documents.
+    pub company: Option<Company>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Company {
+    pub name: String,
+    /// Address, contact and registration lines, in order.
+    #[serde(default)]
+    pub details: Vec<String>,
+}
+
+impl Config {
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = match env::var("MAINT_CONFIG") {
+            Ok(value) => PathBuf::from(value),
+            Err(_) => match env::var("HOME") {
+                Ok(value) => PathBuf::from(format!("{}/.maint.yaml", value)),
+                Err(_) => return Ok(Self::default()),
+            },
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                serde_yaml::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e).into())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e).into()),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::NaiveDate;
+use clap::{Args, ValueEnum};
+use maint::{BurnStatus, DashboardEntry, Store};
+
+use crate::cmd::today_utc;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Args)]
+pub struct Cmd {
+    /// Date to show the dashboard for (YYYY-MM-DD). Defaults to today.
+    #[arg(long, default_value_t = today_utc())]
+    pub date: NaiveDate,
+
+    /// Output format
+    #[arg(long, value_enum, default_value = "text")]
+    pub format: Format,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = ds.dashboard(self.date)?;
+        match self.format {
+            Format::Text => print!("{}", table(&entries)),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+        }
+
+        Ok(())
+    }
+}
+
+fn indicator(status: BurnStatus) -> &'static str {
+    match status {
+        BurnStatus::Ok => "[ ok ]",
+        BurnStatus::Warning => "[WARN]",
+        BurnStatus::Critical => "[CRIT]",
+        BurnStatus::Exhausted => "[OVER]",
+    }
+}
+
+fn table(entries: &[DashboardEntry]) -> String {
+    if entries.is_empty() {
+        return "No active contracts\n".to_string();
+    }
+
+    let header = [
+        "STATUS",
+        "CONTRACT",
+        "CUSTOMER",
+        "END",
+        "DAYS LEFT",
+        "POINTS",
+        "BURN",
+        "ELAPSED",
+    ];
+    let rows: Vec<[String; 8]> = entries
+        .iter()
+        .map(|e| {
+            [
+                indicator(e.status).to_string(),
+                e.contract_id.to_string(),
+                e.customer.clone().unwrap_or_default(),
+                e.end_date.to_string(),
+                e.days_remaining.to_string(),
+                format!("{}/{}", e.points_used, e.total_points),
+                format!("{:.0}%", e.burn_percent),
+                format!("{:.0}%", e.elapsed_percent),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(|h| h.chars().count());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    let mut line = |cells: Vec<&str>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    };
+    line(header.to_vec());
+    for row in &rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+    out
+}
@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use maint::{Entity, Store, Validate};
+
+#[derive(Parser)]
+pub struct Cmd {
+    #[command(subcommand)]
+    pub arg: Arg,
+}
+
+#[derive(Subcommand)]
+pub enum Arg {
+    /// Report inconsistencies in the stored data
+    Check(Check),
+    /// Save a timestamped copy of the database, safe while it is in use
+    Backup(Backup),
+    /// Replace the database with a backup, after checking the backup is intact
+    Restore(Restore),
+}
+
+#[derive(Args)]
+pub struct Check {}
+
+#[derive(Args)]
+pub struct Backup {
+    /// Directory to keep backups in. Defaults to `<database>.backups`.
+    pub dir: Option<PathBuf>,
+    /// How many of the newest backups in the directory to keep
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    pub keep: u32,
+}
+
+#[derive(Args)]
+pub struct Restore {
+    pub file: PathBuf,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.arg {
+            Arg::Check(_arg) => {
+                let overlaps = ds.overlapping_contracts()?;
+                let mut invalid = Vec::new();
+                invalid.extend(failures(Entity::Customer, ds.list_customer()?, |r| r.id));
+                invalid.extend(failures(Entity::Contract, ds.list_contract()?, |r| r.id));
+                invalid.extend(failures(Entity::Request, ds.list_request()?, |r| r.id));
+                invalid.extend(failures(Entity::Work, ds.list_work()?, |r| r.id));
+                if overlaps.is_empty() && invalid.is_empty() {
+                    println!("No problems found");
+                }
+                if !overlaps.is_empty() {
+                    println!("# Contracts of the same customer that overlap in time");
+                    print!("{}", serde_yaml::to_string(&overlaps)?);
+                }
+                if !invalid.is_empty() {
+                    println!("# Records that fail the checks on adding and saving");
+                    for line in invalid {
+                        println!("{}", line);
+                    }
+                }
+            }
+            Arg::Backup(arg) => {
+                let path = ds.backup(arg.dir.as_deref(), arg.keep as usize)?;
+                println!("Backed up to {}", path.display());
+            }
+            Arg::Restore(arg) => {
+                let saved = ds.restore_backup(&arg.file)?;
+                println!("Restored {}", arg.file.display());
+                println!("The database as it was is saved in {}", saved.display());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// One line per problem with `records`, such as records restored from an
+/// export or edited by hand in a directory store.
+fn failures<T: Validate>(entity: Entity, records: Vec<T>, id: fn(&T) -> u32) -> Vec<String> {
+    records
+        .iter()
+        .flat_map(|record| {
+            record
+                .validate()
+                .into_iter()
+                .map(move |error| format!("{} {}: {}", entity, id(record), error))
+        })
+        .collect()
+}
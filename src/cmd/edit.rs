@@ -1,5 +1,10 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
 use clap::{Args, Parser, Subcommand};
-use maint::DataStore;
+use maint::Store;
+
+use crate::cmd::{overlap, overlap_hint, warn_outside_period};
 #[derive(Parser)]
 pub struct Cmd {
     #[command(subcommand)]
@@ -28,6 +33,10 @@ pub struct Customer {
 pub struct Contract {
     /// ID of the contract to edit
     pub id: u32,
+
+    /// Save the contract even if it overlaps another contract of the customer
+    #[arg(long)]
+    pub allow_overlap: bool,
 }
 
 #[derive(Args)]
@@ -43,38 +52,101 @@ pub struct Work {
 }
 
 impl Cmd {
-    pub fn run(&self, ds: &DataStore) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
         match &self.arg {
             Arg::Customer(arg) => {
                 let entity = ds.get_customer(arg.id)?;
-                let updated = crate::get_editor_description(&entity.to_string())?;
-                let mut new = updated.parse::<maint::Customer>()?;
-                new.id = entity.id;
-                let _ = ds.save_customer(new)?;
+                edit_until_saved(&entity, |mut new: maint::Customer| {
+                    new.id = entity.id;
+                    ds.save_customer(new)
+                })?;
             }
             Arg::Contract(arg) => {
                 let entity = ds.get_contract(arg.id)?;
-                let updated = crate::get_editor_description(&entity.to_string())?;
-                let mut new = updated.parse::<maint::Contract>()?;
-                new.id = entity.id;
-                let _ = ds.save_contract(new)?;
+                edit_until_saved(&entity, |mut new: maint::Contract| {
+                    new.id = entity.id;
+                    ds.save_contract(new, overlap(arg.allow_overlap))
+                })?;
             }
             Arg::Request(arg) => {
                 let entity = ds.get_request(arg.id)?;
-                let updated = crate::get_editor_description(&entity.to_string())?;
-                let mut new = updated.parse::<maint::Request>()?;
-                new.id = entity.id;
-                let _ = ds.save_request(new)?;
+                edit_until_saved(&entity, |mut new: maint::Request| {
+                    new.id = entity.id;
+                    let (contract_id, date) = (new.contract_id, new.request_date);
+                    let rows = ds.save_request(new)?;
+                    warn_outside_period(ds, contract_id, date)?;
+                    Ok(rows)
+                })?;
             }
             Arg::Work(arg) => {
                 let entity = ds.get_work(arg.id)?;
-                let updated = crate::get_editor_description(&entity.to_string())?;
-                let mut new = updated.parse::<maint::Work>()?;
-                new.id = entity.id;
-                let _ = ds.save_work(new)?;
+                edit_until_saved(&entity, |mut new: maint::Work| {
+                    new.id = entity.id;
+                    ds.save_work(new)
+                })?;
             }
         }
 
         Ok(())
     }
 }
+
+const ERROR_HEADER: &str = "# maint:";
+
+/// Opens `entity` in the editor until the result parses and `save` accepts
+/// it, re-opening it with the errors on top otherwise. Gives up when the user
+/// saves the text unchanged or empties it.
+fn edit_until_saved<T>(
+    entity: &T,
+    save: impl Fn(T) -> Result<usize, maint::Error>,
+) -> Result<(), Box<dyn std::error::Error>>
+where
+    T: Display + FromStr,
+    T::Err: Display,
+{
+    let mut shown = entity.to_string();
+    let mut header = String::new();
+    loop {
+        let edited = crate::get_editor_description(&format!("{}{}", header, shown))?;
+        let edited = strip_error_header(&edited);
+        if edited.trim().is_empty() || edited.trim() == shown.trim() {
+            eprintln!("No changes made, aborting");
+            return Ok(());
+        }
+
+        let error = match edited.parse::<T>() {
+            Ok(new) => match save(new) {
+                Ok(_) => return Ok(()),
+                Err(maint::Error::Validation(errors)) => errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(e @ maint::Error::Overlap(_)) => overlap_hint(e).to_string(),
+                Err(e) => return Err(e.into()),
+            },
+            Err(e) => e.to_string(),
+        };
+        header = error_header(&error);
+        shown = edited;
+    }
+}
+
+fn error_header(error: &str) -> String {
+    let mut header = format!("{} could not save this record:\n", ERROR_HEADER);
+    for line in error.lines() {
+        header.push_str(&format!("{}   {}\n", ERROR_HEADER, line));
+    }
+    header.push_str(&format!(
+        "{} fix it and save, or save it unchanged or empty to abort.\n",
+        ERROR_HEADER
+    ));
+    header
+}
+
+fn strip_error_header(text: &str) -> String {
+    text.lines()
+        .skip_while(|line| line.starts_with(ERROR_HEADER))
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
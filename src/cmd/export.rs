@@ -0,0 +1,37 @@
+use std::path::PathBuf;
+
+use clap::{Args, ValueEnum};
+use maint::Store;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Json,
+    Yaml,
+}
+
+#[derive(Args)]
+pub struct Cmd {
+    /// Document format
+    #[arg(long, value_enum, default_value = "json")]
+    pub format: Format,
+
+    /// Write to this file instead of standard output
+    #[arg(long, short, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let export = ds.export()?;
+        let text = match self.format {
+            Format::Json => serde_json::to_string_pretty(&export)? + "\n",
+            Format::Yaml => serde_yaml::to_string(&export)?,
+        };
+        match &self.output {
+            Some(path) => std::fs::write(path, text)?,
+            None => print!("{}", text),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,20 @@
+use clap::Args;
+use maint::{Entity, Store};
+
+#[derive(Args)]
+pub struct Cmd {
+    /// Kind of record: customer, contract, request, work, note or attachment
+    pub entity: Entity,
+
+    /// ID of the record
+    pub id: u32,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let entries = ds.history(self.entity, self.id)?;
+        print!("{}", serde_yaml::to_string(&entries)?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+use std::io::Write;
+use std::{collections::HashMap, path::PathBuf};
+
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
+use csv::StringRecord;
+use maint::{
+    Contract, Customer, Entity, Error, Export, ImportSummary, Request, RowError, Store, Work,
+};
+
+use crate::cmd::overlap;
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cmd {
+    #[command(subcommand)]
+    pub arg: Option<Arg>,
+
+    /// Document written by `maint export`, as JSON or YAML
+    #[arg(required = true)]
+    pub file: Option<PathBuf>,
+
+    /// Add the records to a database that already has some, with new ids
+    #[arg(long)]
+    pub merge: bool,
+
+    /// Check the document without saving anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Merge contracts even if they overlap others of the same customer
+    #[arg(long)]
+    pub allow_overlap: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Arg {
+    /// Add records from a CSV file with a header row, one record per row
+    Csv(Csv),
+}
+
+#[derive(Args)]
+pub struct Csv {
+    /// Kind of record in the file: customer, contract, request or work
+    #[arg(long)]
+    pub entity: Entity,
+
+    /// CSV file to read
+    pub file: PathBuf,
+
+    /// Read a field from a column with another name (e.g. points_used=Hours).
+    /// Columns are otherwise named after the fields.
+    #[arg(long = "map", value_name = "FIELD=COLUMN", value_parser = parse_mapping)]
+    pub mapping: Vec<(String, String)>,
+
+    /// strftime format of the dates in the file
+    #[arg(long, default_value = "%Y-%m-%d")]
+    pub date_format: String,
+
+    /// Field separator
+    #[arg(long, default_value_t = ',')]
+    pub delimiter: char,
+
+    /// Show what would be imported without saving anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Import contracts even if they overlap others of the same customer
+    #[arg(long)]
+    pub allow_overlap: bool,
+}
+
+fn parse_mapping(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((field, column)) => Ok((field.trim().to_string(), column.trim().to_string())),
+        None => Err(format!("expected FIELD=COLUMN, got {}", s)),
+    }
+}
+
+/// The columns each kind of record is imported from.
+fn fields(entity: Entity) -> Result<&'static [&'static str], String> {
+    match entity {
+        Entity::Customer => Ok(&["name"]),
+        Entity::Contract => Ok(&["customer_id", "start_date", "end_date", "total_points"]),
+        Entity::Request => Ok(&["contract_id", "description", "request_date"]),
+        Entity::Work => Ok(&[
+            "request_id",
+            "worker",
+            "description",
+            "points_used",
+            "work_date",
+        ]),
+        _ => Err(format!("cannot import {} records", entity)),
+    }
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        match (&self.arg, &self.file) {
+            (Some(Arg::Csv(arg)), _) => arg.run(ds),
+            (None, Some(file)) => {
+                let text = std::fs::read_to_string(file)?;
+                // JSON is valid YAML, so one parser reads both.
+                let export: Export = serde_yaml::from_str(&text)
+                    .map_err(|e| format!("{}: {}", file.display(), e))?;
+                let mut summary = ImportSummary::default();
+                ds.batch(self.dry_run, &mut || {
+                    summary = ds.import(&export, self.merge, overlap(self.allow_overlap))?;
+                    Ok(())
+                })?;
+                if self.dry_run {
+                    println!("Would import:");
+                } else {
+                    println!("Imported:");
+                }
+                print!("{}", serde_yaml::to_string(&summary)?);
+                Ok(())
+            }
+            (None, None) => unreachable!("clap requires a file without a subcommand"),
+        }
+    }
+}
+
+impl Csv {
+    fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        self.import(ds, &mut std::io::stdout())
+    }
+
+    /// Imports the file, reporting to `out`.
+    fn import(
+        &self,
+        ds: &dyn Store,
+        out: &mut dyn Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let fields = fields(self.entity)?;
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(u8::try_from(self.delimiter).map_err(|_| "delimiter must be ASCII")?)
+            .from_path(&self.file)?;
+        let headers = reader.headers()?.clone();
+
+        for (field, _) in &self.mapping {
+            if !fields.contains(&field.as_str()) {
+                return Err(format!("{} records have no field {}", self.entity, field).into());
+            }
+        }
+        let mapping: HashMap<&str, &str> = self
+            .mapping
+            .iter()
+            .map(|(field, column)| (field.as_str(), column.as_str()))
+            .collect();
+        let mut columns = HashMap::new();
+        for field in fields {
+            let column = mapping.get(field).copied().unwrap_or(field);
+            match headers.iter().position(|h| h.trim() == column) {
+                Some(index) => columns.insert(*field, index),
+                None => return Err(format!("no column {} for {}", column, field).into()),
+            };
+        }
+
+        let mut count = 0;
+        let mut written = Ok(());
+        ds.batch(self.dry_run, &mut || {
+            let mut errors = Vec::new();
+            for record in reader.records() {
+                let (row, result) = match record {
+                    Ok(record) => {
+                        let row = Row {
+                            record: &record,
+                            columns: &columns,
+                            date_format: &self.date_format,
+                        };
+                        let result = self.add(ds, &row).map_err(|e| e.replace("\n  ", " "));
+                        (record.position().map_or(0, |p| p.line()), result)
+                    }
+                    Err(e) => (e.position().map_or(0, |p| p.line()), Err(e.to_string())),
+                };
+                match result {
+                    Ok(preview) => {
+                        if self.dry_run && written.is_ok() {
+                            written = writeln!(out, "---\n{}", preview.trim_end());
+                        }
+                        count += 1;
+                    }
+                    Err(message) => errors.push(RowError {
+                        section: None,
+                        row,
+                        message,
+                    }),
+                }
+            }
+            if !errors.is_empty() {
+                return Err(Error::Rows(errors));
+            }
+            Ok(())
+        })?;
+        written?;
+
+        if self.dry_run {
+            writeln!(out, "Would import {} {} records", count, self.entity)?;
+        } else {
+            writeln!(out, "Imported {} {} records", count, self.entity)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds the record in `row`, returning it as YAML.
+    fn add(&self, ds: &dyn Store, row: &Row) -> Result<String, String> {
+        let e = |e: Error| e.to_string();
+        match self.entity {
+            Entity::Customer => {
+                let customer = Customer {
+                    id: 0,
+                    name: row.text("name"),
+                };
+                ds.add_customer(&customer.name).map_err(e)?;
+                Ok(customer.to_string())
+            }
+            Entity::Contract => {
+                let contract = Contract {
+                    id: 0,
+                    customer_id: row.number("customer_id")?,
+                    start_date: row.date("start_date")?,
+                    end_date: row.date("end_date")?,
+                    total_points: row.number("total_points")?,
+                };
+                ds.add_contract(
+                    contract.customer_id,
+                    &contract.start_date,
+                    &contract.end_date,
+                    contract.total_points,
+                    overlap(self.allow_overlap),
+                )
+                .map_err(e)?;
+                Ok(contract.to_string())
+            }
+            Entity::Request => {
+                let request = Request {
+                    id: 0,
+                    contract_id: row.number("contract_id")?,
+                    description: row.text("description"),
+                    request_date: row.date("request_date")?,
+                };
+                ds.add_request(
+                    request.contract_id,
+                    &request.description,
+                    &request.request_date,
+                )
+                .map_err(e)?;
+                Ok(request.to_string())
+            }
+            Entity::Work => {
+                let work = Work {
+                    id: 0,
+                    request_id: row.number("request_id")?,
+                    worker: row.text("worker"),
+                    description: row.text("description"),
+                    points_used: row.number("points_used")?,
+                    work_date: row.date("work_date")?,
+                };
+                ds.add_work(
+                    work.request_id,
+                    &work.worker,
+                    &work.description,
+                    work.points_used,
+                    &work.work_date,
+                )
+                .map_err(e)?;
+                Ok(work.to_string())
+            }
+            _ => unreachable!("fields() rejects other entities"),
+        }
+    }
+}
+
+/// One CSV record, read by field name.
+struct Row<'a> {
+    record: &'a StringRecord,
+    columns: &'a HashMap<&'static str, usize>,
+    date_format: &'a str,
+}
+
+impl Row<'_> {
+    fn text(&self, field: &str) -> String {
+        self.record
+            .get(self.columns[field])
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    }
+
+    fn number(&self, field: &str) -> Result<u32, String> {
+        let value = self.text(field);
+        value
+            .parse()
+            .map_err(|_| format!("{}: not a number: {:?}", field, value))
+    }
+
+    fn date(&self, field: &str) -> Result<NaiveDate, String> {
+        let value = self.text(field);
+        NaiveDate::parse_from_str(&value, self.date_format).map_err(|e| {
+            format!(
+                "{}: {:?} does not match {}: {}",
+                field, value, self.date_format, e
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maint::{MemoryStore, Overlap};
+
+    use super::*;
+
+    /// Imports `text` as a CSV file of `entity` records, returning the output.
+    fn import(
+        ds: &dyn Store,
+        entity: Entity,
+        text: &str,
+        mapping: &[(&str, &str)],
+        dry_run: bool,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("import.csv");
+        std::fs::write(&file, text).unwrap();
+        let csv = Csv {
+            entity,
+            file,
+            mapping: mapping
+                .iter()
+                .map(|(field, column)| (field.to_string(), column.to_string()))
+                .collect(),
+            date_format: "%d.%m.%Y".to_string(),
+            delimiter: ';',
+            dry_run,
+            allow_overlap: false,
+        };
+        let mut out = Vec::new();
+        csv.import(ds, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn store() -> MemoryStore {
+        let ds = MemoryStore::new();
+        ds.add_customer("ACME").unwrap();
+        let (start, end) = ("2025-01-01".parse().unwrap(), "2025-12-31".parse().unwrap());
+        ds.add_contract(1, &start, &end, 100, Overlap::Reject)
+            .unwrap();
+        ds.add_request(1, "Mailserver", &start).unwrap();
+        ds.add_request(1, "Backups", &start).unwrap();
+        ds
+    }
+
+    #[test]
+    fn test_csv_mapped_columns() {
+        let ds = store();
+        let text = "Ticket;Who;What;Hours;Day\n\
+                    1;bob;Updates;2;03.02.2025\n\
+                    2; alice ;Restore test;3;04.02.2025\n";
+        let mapping = [
+            ("request_id", "Ticket"),
+            ("worker", "Who"),
+            ("description", "What"),
+            ("points_used", "Hours"),
+            ("work_date", "Day"),
+        ];
+        let out = import(&ds, Entity::Work, text, &mapping, false).unwrap();
+        assert_eq!(out, "Imported 2 work records\n");
+        let work = ds.list_work().unwrap();
+        assert_eq!(work.len(), 2);
+        assert_eq!(work[1].request_id, 2);
+        assert_eq!(work[1].worker, "alice");
+        assert_eq!(work[1].points_used, 3);
+        assert_eq!(work[1].work_date, "2025-02-04".parse().unwrap());
+    }
+
+    #[test]
+    fn test_csv_bad_dates() {
+        let ds = store();
+        let text = "contract_id;description;request_date\n\
+                    1;Firewall;05.02.2025\n\
+                    1;VPN;2025-02-06\n";
+        let e = import(&ds, Entity::Request, text, &[], false).unwrap_err();
+        let Some(Error::Rows(errors)) = e.downcast_ref::<Error>() else {
+            panic!("unexpected error: {}", e);
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].row, 3);
+        assert!(
+            errors[0]
+                .message
+                .starts_with("request_date: \"2025-02-06\"")
+        );
+        // Nothing is saved when a row fails.
+        assert_eq!(ds.list_request().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_csv_missing_column() {
+        let ds = store();
+        let text = "request_id;worker;description;points;work_date\n\
+                    1;bob;Updates;2;03.02.2025\n";
+        let e = import(&ds, Entity::Work, text, &[("points_used", "Hours")], false).unwrap_err();
+        assert_eq!(e.to_string(), "no column Hours for points_used");
+        let e = import(&ds, Entity::Work, text, &[("hours", "points")], false).unwrap_err();
+        assert_eq!(e.to_string(), "work records have no field hours");
+        assert!(ds.list_work().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_csv_dry_run() {
+        let ds = store();
+        let text = "name\nGlobex\nInitech\n";
+        let out = import(&ds, Entity::Customer, text, &[], true).unwrap();
+        assert_eq!(
+            out,
+            "---\nname: Globex\n---\nname: Initech\nWould import 2 customer records\n"
+        );
+        assert_eq!(ds.list_customer().unwrap().len(), 1);
+    }
+}
@@ -1,5 +1,5 @@
 use clap::{Args, Parser, Subcommand};
-use maint::DataStore;
+use maint::Store;
 
 #[derive(Parser)]
 pub struct Cmd {
@@ -25,7 +25,7 @@ pub struct Request {}
 pub struct Work {}
 
 impl Cmd {
-    pub fn run(&self, ds: &DataStore) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
         match &self.arg {
             Arg::Customer(_arg) => println!("{:?}", ds.list_customer()?),
             Arg::Contract(_arg) => println!("{:?}", ds.list_contract()?),
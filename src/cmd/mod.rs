@@ -1,10 +1,24 @@
 use clap::{Args, Parser, Subcommand};
 
 mod add;
+mod attach;
+mod chart;
+mod config;
+mod dashboard;
+mod db;
 mod edit;
+mod export;
+mod history;
+mod import;
 mod list;
+mod note;
+mod pdf;
+mod report;
 mod rm;
+mod search;
 mod show;
+mod trash;
+mod undo;
 mod usage;
 
 #[derive(Parser)]
@@ -14,17 +28,29 @@ pub struct Cli {
 }
 
 impl Cli {
-    pub fn run(&self, ds: &maint::DataStore) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn run(&self, ds: &dyn maint::Store) -> Result<(), Box<dyn std::error::Error>> {
         match &self.command {
             Command::Add(cmd) => cmd.run(ds),
             Command::Rm(cmd) => cmd.run(ds),
             Command::List(cmd) => cmd.run(ds),
             Command::Show(cmd) => cmd.run(ds),
             Command::Edit(cmd) => cmd.run(ds),
-            Command::Usage(cmd) => {
-                println!("{}", ds.usage(cmd.contract_id, cmd.date)?);
-                Ok(())
-            }
+            Command::Usage(cmd) => cmd.run(ds),
+            Command::Search(cmd) => cmd.run(ds),
+            Command::Note(cmd) => cmd.run(ds),
+            Command::Attach(cmd) => cmd.run(ds),
+            Command::Attachments(cmd) => cmd.run(ds),
+            Command::Attachment(cmd) => cmd.run(ds),
+            Command::History(cmd) => cmd.run(ds),
+            Command::Undo(cmd) => cmd.run(ds),
+            Command::Trash(cmd) => cmd.run(ds),
+            Command::Restore(cmd) => cmd.run(ds),
+            Command::Purge(cmd) => cmd.run(ds),
+            Command::Db(cmd) => cmd.run(ds),
+            Command::Report(cmd) => cmd.run(ds),
+            Command::Dashboard(cmd) => cmd.run(ds),
+            Command::Export(cmd) => cmd.run(ds),
+            Command::Import(cmd) => cmd.run(ds),
         }?;
 
         Ok(())
@@ -39,6 +65,21 @@ pub enum Command {
     Show(show::Cmd),
     Edit(edit::Cmd),
     Usage(usage::Usage),
+    Search(search::Cmd),
+    Note(note::Cmd),
+    Attach(attach::Attach),
+    Attachments(attach::Attachments),
+    Attachment(attach::Cmd),
+    History(history::Cmd),
+    Undo(undo::Cmd),
+    Trash(trash::Trash),
+    Restore(trash::Restore),
+    Purge(trash::Purge),
+    Db(db::Cmd),
+    Report(report::Cmd),
+    Dashboard(dashboard::Cmd),
+    Export(export::Cmd),
+    Import(import::Cmd),
 }
 
 #[derive(Args)]
@@ -84,3 +125,99 @@ struct WorkArgs {
 fn today_utc() -> chrono::NaiveDate {
     chrono::Utc::now().date_naive()
 }
+
+/// The overlap policy for an `--allow-overlap` flag.
+fn overlap(allow: bool) -> maint::Overlap {
+    if allow {
+        maint::Overlap::Allow
+    } else {
+        maint::Overlap::Reject
+    }
+}
+
+/// Points users of `add`/`edit contract` at the flag that accepts an overlap.
+fn overlap_hint(e: maint::Error) -> Box<dyn std::error::Error> {
+    match e {
+        maint::Error::Overlap(_) => format!("{}; use --allow-overlap to save it anyway", e).into(),
+        e => e.into(),
+    }
+}
+
+/// Tells the user when a request is dated outside the period of its contract,
+/// where `usage` will not count it or its work.
+fn warn_outside_period(
+    ds: &dyn maint::Store,
+    contract_id: u32,
+    request_date: chrono::NaiveDate,
+) -> Result<(), maint::Error> {
+    if let Some(contract) = ds.outside_contract_period(contract_id, request_date)? {
+        eprintln!(
+            "Warning: request date {} is outside the period of contract {} ({} to {})",
+            request_date, contract.id, contract.start_date, contract.end_date
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use maint::{MemoryStore, Store};
+
+    use super::Cli;
+
+    fn run(ds: &dyn Store, args: &[&str]) {
+        let cli =
+            Cli::try_parse_from(std::iter::once("maint").chain(args.iter().copied())).unwrap();
+        cli.run(ds).unwrap();
+    }
+
+    #[test]
+    fn test_add_contract_end_date() {
+        let ds = MemoryStore::new();
+        run(&ds, &["add", "customer", "--name", "ACME"]);
+        run(
+            &ds,
+            &[
+                "add",
+                "contract",
+                "1",
+                "--start-date",
+                "2025-01-01",
+                "--end-date",
+                "2025-12-31",
+                "--total-points",
+                "10",
+            ],
+        );
+        let contract = ds.get_contract(1).unwrap();
+        assert_eq!(contract.start_date.to_string(), "2025-01-01");
+        assert_eq!(contract.end_date.to_string(), "2025-12-31");
+    }
+
+    #[test]
+    fn test_show_request_without_notes() {
+        let ds = MemoryStore::new();
+        run(&ds, &["add", "customer", "--name", "ACME"]);
+        run(
+            &ds,
+            &[
+                "add",
+                "contract",
+                "1",
+                "--start-date",
+                "2025-01-01",
+                "--end-date",
+                "2025-12-31",
+                "--total-points",
+                "10",
+            ],
+        );
+        run(
+            &ds,
+            &["add", "request", "1", "2025-02-01", "--description", "mail"],
+        );
+        // The memory store has no notes, which must not keep the request hidden.
+        run(&ds, &["show", "request", "1"]);
+    }
+}
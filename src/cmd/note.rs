@@ -0,0 +1,32 @@
+use clap::Args;
+use maint::{Store, current_user};
+
+#[derive(Args)]
+pub struct Cmd {
+    /// ID of the request to add a note to
+    pub request_id: u32,
+
+    /// Author of the note. Defaults to the current user.
+    #[arg(long, default_value_t = current_user())]
+    pub author: String,
+
+    /// Text of the note. Opens the editor if omitted.
+    #[arg(long)]
+    pub body: Option<String>,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let request = ds.get_request(self.request_id)?;
+        let body = match self.body {
+            Some(ref body) => body,
+            None => &crate::get_editor_description("")?,
+        };
+        if body.trim().is_empty() {
+            return Err("empty note, aborting".into());
+        }
+        ds.add_note(request.id, &self.author, body)?;
+
+        Ok(())
+    }
+}
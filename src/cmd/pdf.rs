@@ -0,0 +1,319 @@
+use std::path::Path;
+
+use maint::{ContractReport, UsageStatement};
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Point,
+};
+
+use crate::cmd::config::Config;
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const FONT_SIZE: f32 = 9.0;
+const LINE_HEIGHT: f32 = 5.0;
+/// Rough width of an average Helvetica character at `FONT_SIZE`, for cutting
+/// text to fit its column.
+const CHAR_WIDTH: f32 = 1.7;
+
+/// A4 pages with the company header on each, filled top to bottom.
+struct Writer<'a> {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    config: &'a Config,
+    page: usize,
+    y: f32,
+}
+
+impl<'a> Writer<'a> {
+    fn new(title: &str, config: &'a Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let (doc, page, layer) =
+            PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "content");
+        let layer = doc.get_page(page).get_layer(layer);
+        let regular = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+        let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+        let mut writer = Self {
+            doc,
+            layer,
+            regular,
+            bold,
+            config,
+            page: 1,
+            y: 0.0,
+        };
+        writer.header();
+        Ok(writer)
+    }
+
+    fn header(&mut self) {
+        self.y = PAGE_HEIGHT - MARGIN;
+        if let Some(company) = &self.config.company {
+            self.text(&company.name, 12.0, MARGIN, &self.bold);
+            self.y -= LINE_HEIGHT + 1.0;
+            for detail in &company.details {
+                self.text(detail, 8.0, MARGIN, &self.regular);
+                self.y -= LINE_HEIGHT - 1.0;
+            }
+            self.rule();
+        }
+        self.layer.use_text(
+            format!("Page {}", self.page),
+            8.0,
+            Mm(PAGE_WIDTH - MARGIN - 12.0),
+            Mm(MARGIN / 2.0),
+            &self.regular,
+        );
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "content");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.page += 1;
+        self.header();
+    }
+
+    /// Starts a new page unless `height` more millimetres fit on this one.
+    fn reserve(&mut self, height: f32) -> bool {
+        if self.y - height < MARGIN {
+            self.new_page();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Writes `text` at `x` on the current line. The builtin fonts only have
+    /// the Windows-1252 characters, so anything else shows as `?` rather than
+    /// silently going missing.
+    fn text(&self, text: &str, size: f32, x: f32, font: &IndirectFontRef) {
+        self.layer
+            .use_text(win_ansi(text), size, Mm(x), Mm(self.y), font);
+    }
+
+    fn rule(&mut self) {
+        self.layer.add_line(Line {
+            points: vec![
+                (Point::new(Mm(MARGIN), Mm(self.y)), false),
+                (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(self.y)), false),
+            ],
+            is_closed: false,
+        });
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn title(&mut self, text: &str) {
+        self.reserve(3.0 * LINE_HEIGHT);
+        self.y -= 2.0;
+        self.text(text, 14.0, MARGIN, &self.bold);
+        self.y -= 2.0 * LINE_HEIGHT;
+    }
+
+    fn heading(&mut self, text: &str) {
+        self.reserve(3.0 * LINE_HEIGHT);
+        self.text(text, 11.0, MARGIN, &self.bold);
+        self.y -= 1.5 * LINE_HEIGHT;
+    }
+
+    /// Label and value pairs, one per line.
+    fn fields(&mut self, fields: &[(&str, String)]) {
+        for (label, value) in fields {
+            self.reserve(LINE_HEIGHT);
+            self.text(label, FONT_SIZE, MARGIN, &self.bold);
+            self.text(value, FONT_SIZE, MARGIN + 40.0, &self.regular);
+            self.y -= LINE_HEIGHT;
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    /// A table with columns `widths` millimetres wide, repeating the header row
+    /// at the top of every page it runs onto.
+    fn table(&mut self, header: &[&str], widths: &[f32], rows: &[Vec<String>]) {
+        self.reserve(2.0 * LINE_HEIGHT);
+        self.row(header, widths, true);
+        for row in rows {
+            if self.reserve(LINE_HEIGHT) {
+                self.row(header, widths, true);
+            }
+            let cells: Vec<&str> = row.iter().map(String::as_str).collect();
+            self.row(&cells, widths, false);
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn row(&mut self, cells: &[&str], widths: &[f32], bold: bool) {
+        let font = if bold { &self.bold } else { &self.regular };
+        let mut x = MARGIN;
+        for (cell, width) in cells.iter().zip(widths) {
+            self.text(&fit(cell, *width), FONT_SIZE, x, font);
+            x += width;
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn save(self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, self.doc.save_to_bytes()?)?;
+        Ok(())
+    }
+}
+
+/// Characters of Windows-1252 outside Latin-1, which the builtin fonts have
+/// besides ASCII and Latin-1.
+const WIN_ANSI_EXTRA: &str = "€‚ƒ„…†‡ˆ‰Š‹ŒŽ‘’“”•–—˜™š›œžŸ";
+
+/// `text` with the characters the builtin fonts lack replaced by `?`.
+fn win_ansi(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c,
+            c if WIN_ANSI_EXTRA.contains(c) => c,
+            _ => '?',
+        })
+        .collect()
+}
+
+/// Cuts `text` short enough to fit in `width` millimetres.
+fn fit(text: &str, width: f32) -> String {
+    let max = ((width - 2.0) / CHAR_WIDTH).max(3.0) as usize;
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max - 3).collect();
+        format!("{}...", cut)
+    }
+}
+
+/// A usage statement for `customer`, with one row per work entry.
+pub fn statement(
+    path: &Path,
+    config: &Config,
+    customer: &str,
+    statement: &UsageStatement,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut w = Writer::new("Usage statement", config)?;
+    w.title(&format!("Usage statement: {}", customer));
+    w.fields(&[
+        ("Contract", statement.contract_id.to_string()),
+        (
+            "Contract period",
+            format!("{} to {}", statement.start_date, statement.end_date),
+        ),
+        (
+            "Statement period",
+            format!("{} to {}", statement.from, statement.to),
+        ),
+        ("Total points", statement.total_points.to_string()),
+        ("Opening balance", statement.opening_balance.to_string()),
+        ("Points used", statement.points_used.to_string()),
+        ("Closing balance", statement.closing_balance.to_string()),
+    ]);
+
+    w.heading("Work");
+    let rows: Vec<Vec<String>> = statement
+        .cumulative_usage
+        .iter()
+        .map(|u| {
+            vec![
+                u.work_date.to_string(),
+                u.request_description.clone(),
+                u.worker.clone(),
+                u.work_description.clone(),
+                u.points_used.to_string(),
+                u.cumulative_points_used.to_string(),
+            ]
+        })
+        .collect();
+    w.table(
+        &["Date", "Request", "Worker", "Work", "Points", "Total"],
+        &[22.0, 42.0, 28.0, 52.0, 13.0, 13.0],
+        &rows,
+    );
+
+    w.save(path)
+}
+
+/// A contract report: terms, totals, and every request with its work.
+pub fn contract_report(
+    path: &Path,
+    config: &Config,
+    report: &ContractReport,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let contract = &report.contract;
+    let mut w = Writer::new("Contract report", config)?;
+    w.title(&format!("Contract report: {}", report.customer));
+    w.fields(&[
+        ("Contract", contract.contract_id.to_string()),
+        (
+            "Period",
+            format!("{} to {}", contract.start_date, contract.end_date),
+        ),
+        ("As of", report.date.to_string()),
+        ("Total points", contract.total_points.to_string()),
+        ("Points used", contract.points_used.to_string()),
+        ("Points remaining", contract.points_remaining.to_string()),
+    ]);
+
+    w.heading("Requests");
+    let mut rows = Vec::new();
+    for request in &report.requests {
+        rows.push(vec![
+            request.request_date.to_string(),
+            request.description.clone(),
+            request.status.to_string(),
+            request.points_used.to_string(),
+        ]);
+        for work in &request.work {
+            rows.push(vec![
+                format!("  {}", work.work_date),
+                format!("  {}: {}", work.worker, work.description),
+                String::new(),
+                work.points_used.to_string(),
+            ]);
+        }
+    }
+    w.table(
+        &["Date", "Request / work", "Status", "Points"],
+        &[25.0, 105.0, 25.0, 15.0],
+        &rows,
+    );
+
+    w.save(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use maint::{MemoryStore, Overlap, Store};
+    use printpdf::lopdf;
+
+    use super::*;
+
+    #[test]
+    fn test_statement_pages() {
+        let ds = MemoryStore::new();
+        ds.add_customer("ACME").unwrap();
+        let (start, end) = ("2025-01-01".parse().unwrap(), "2025-12-31".parse().unwrap());
+        ds.add_contract(1, &start, &end, 500, Overlap::Reject)
+            .unwrap();
+        ds.add_request(1, "Mailserver", &start).unwrap();
+        for _ in 0..120 {
+            ds.add_work(1, "bob", "Updates", 1, &start).unwrap();
+        }
+        let usage = ds.usage_between(1, start, end).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("statement.pdf");
+        statement(&path, &Config::default(), "ACME", &usage).unwrap();
+        let doc = lopdf::Document::load(&path).unwrap();
+        assert_eq!(doc.get_pages().len(), 3);
+    }
+
+    #[test]
+    fn test_win_ansi() {
+        assert_eq!(win_ansi("Café – 10 €"), "Café – 10 €");
+        assert_eq!(win_ansi("Zürich ✓ 東京"), "Zürich ? ??");
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
+use maint::Store;
+use minijinja::{Environment, context};
+
+use crate::cmd::{chart, config::Config, pdf, today_utc};
+
+const CONTRACT_TEMPLATE: &str = include_str!("../../templates/contract_report.html");
+
+#[derive(Parser)]
+pub struct Cmd {
+    #[command(subcommand)]
+    pub arg: Arg,
+}
+
+#[derive(Subcommand)]
+pub enum Arg {
+    /// List work that no contract's usage counts
+    Unaccounted(Unaccounted),
+    /// Report on one contract: terms, consumption, requests and work
+    Contract(Contract),
+}
+
+#[derive(Args)]
+pub struct Unaccounted {}
+
+#[derive(Args)]
+pub struct Contract {
+    /// ID of the contract to report on
+    pub contract_id: u32,
+
+    /// Date to report as of (YYYY-MM-DD). Defaults to today.
+    #[arg(long, default_value_t = today_utc())]
+    pub date: NaiveDate,
+
+    /// Write the report as HTML to this file
+    #[arg(long, value_name = "FILE")]
+    pub html: Option<PathBuf>,
+
+    /// Write the burn-down chart as SVG to this file
+    #[arg(long, value_name = "FILE")]
+    pub svg: Option<PathBuf>,
+
+    /// Write the report as PDF to this file
+    #[arg(long, value_name = "FILE")]
+    pub pdf: Option<PathBuf>,
+
+    /// Render the HTML with this template instead of the built-in one
+    #[arg(long, value_name = "FILE", requires = "html")]
+    pub template: Option<PathBuf>,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        match &self.arg {
+            Arg::Unaccounted(_arg) => {
+                print!("{}", serde_yaml::to_string(&ds.unaccounted_work()?)?);
+            }
+            Arg::Contract(arg) => arg.run(ds)?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Contract {
+    fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let report = ds.contract_report(self.contract_id, self.date)?;
+        let svg = chart::burn_down_svg(&report.usage, self.date);
+
+        if let Some(path) = &self.svg {
+            std::fs::write(path, &svg)?;
+        }
+        if let Some(path) = &self.html {
+            let template = match &self.template {
+                Some(path) => std::fs::read_to_string(path)?,
+                None => CONTRACT_TEMPLATE.to_string(),
+            };
+            let mut env = Environment::new();
+            env.add_template("contract.html", &template)?;
+            let html = env.get_template("contract.html")?.render(context! {
+                chart => svg,
+                ..minijinja::Value::from_serialize(&report)
+            })?;
+            std::fs::write(path, html)?;
+        }
+        if let Some(path) = &self.pdf {
+            pdf::contract_report(path, &Config::load()?, &report)?;
+        }
+        if self.html.is_none() && self.svg.is_none() && self.pdf.is_none() {
+            print!("{}", report);
+        }
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use clap::{Args, Parser, Subcommand};
-use maint::DataStore;
+use maint::{Entity, Store};
 
 #[derive(Parser)]
 pub struct Cmd {
@@ -40,12 +40,17 @@ pub struct Work {
 }
 
 impl Cmd {
-    pub fn run(&self, _ds: &DataStore) -> Result<(), Box<dyn std::error::Error>> {
-        match &self.arg {
-            Arg::Customer(_arg) => todo!(),
-            Arg::Contract(_arg) => todo!(),
-            Arg::Request(_arg) => todo!(),
-            Arg::Work(_arg) => todo!(),
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let (entity, id) = match &self.arg {
+            Arg::Customer(arg) => (Entity::Customer, arg.id),
+            Arg::Contract(arg) => (Entity::Contract, arg.id),
+            Arg::Request(arg) => (Entity::Request, arg.id),
+            Arg::Work(arg) => (Entity::Work, arg.id),
+        };
+        if ds.delete(entity, id)? == 0 {
+            println!("{} {} is already in the trash", entity, id);
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,22 @@
+use clap::Args;
+use maint::Store;
+
+#[derive(Args)]
+pub struct Cmd {
+    /// Words to look for in customer names and request and work descriptions
+    #[arg(required = true)]
+    pub query: Vec<String>,
+
+    /// Maximum number of results to show
+    #[arg(long, default_value = "20")]
+    pub limit: u32,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let hits = ds.search(&self.query.join(" "), self.limit)?;
+        print!("{}", serde_yaml::to_string(&hits)?);
+
+        Ok(())
+    }
+}
@@ -1,5 +1,5 @@
 use clap::{Args, Parser, Subcommand};
-use maint::DataStore;
+use maint::{Error, Store};
 
 #[derive(Parser)]
 pub struct Cmd {
@@ -40,13 +40,23 @@ pub struct Work {
 }
 
 impl Cmd {
-    pub fn run(&self, ds: &DataStore) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
         match &self.arg {
             Arg::Customer(arg) => println!("{:?}", ds.get_customer(arg.id)?),
             Arg::Contract(arg) => {
                 println!("{:?}", ds.get_contract(arg.id)?);
             }
-            Arg::Request(arg) => println!("{:?}", ds.get_request(arg.id)?),
+            Arg::Request(arg) => {
+                println!("{:?}", ds.get_request(arg.id)?);
+                // Stores without notes still show the request.
+                let notes = match ds.list_notes(arg.id) {
+                    Err(Error::Unsupported(_)) => Vec::new(),
+                    notes => notes?,
+                };
+                for note in notes {
+                    println!("\n{}", note);
+                }
+            }
             Arg::Work(arg) => println!("{:?}", ds.get_work(arg.id)?),
         };
 
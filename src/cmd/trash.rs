@@ -0,0 +1,102 @@
+use chrono::{Duration, Utc};
+use clap::Args;
+use maint::{Entity, Store};
+
+#[derive(Args)]
+pub struct Trash {}
+
+impl Trash {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        print!("{}", serde_yaml::to_string(&ds.list_trash()?)?);
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct Restore {
+    /// Kind of record: customer, contract, request or work
+    pub entity: Entity,
+
+    /// ID of the record
+    pub id: u32,
+}
+
+impl Restore {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        if ds.restore(self.entity, self.id)? == 0 {
+            println!("{} {} is not in the trash", self.entity, self.id);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+pub struct Purge {
+    /// Only purge records deleted longer ago than this (e.g. 30d, 12w, 1y)
+    #[arg(long, value_parser = parse_age)]
+    pub older_than: Duration,
+}
+
+impl Purge {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let cutoff = Utc::now()
+            .checked_sub_signed(self.older_than)
+            .ok_or("--older-than goes back too far")?;
+        for (entity, id) in ds.purge(cutoff)? {
+            println!("Purged {} {}", entity, id);
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses an age such as `365d`, `12w` or `1y`, of at least one unit.
+fn parse_age(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid age '{}', expected e.g. 30d, 12w or 1y", s);
+    let count = |rest: &str| match rest.parse::<i64>() {
+        Ok(count) if count >= 1 => Ok(count),
+        _ => Err(invalid()),
+    };
+    let age = if let Some(rest) = s.strip_suffix('d') {
+        Duration::try_days(count(rest)?)
+    } else if let Some(rest) = s.strip_suffix('w') {
+        Duration::try_weeks(count(rest)?)
+    } else if let Some(rest) = s.strip_suffix('y') {
+        count(rest)?.checked_mul(365).and_then(Duration::try_days)
+    } else {
+        return Err(invalid());
+    };
+    age.ok_or_else(|| format!("age '{}' is too long", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d"), Ok(Duration::days(30)));
+        assert_eq!(parse_age("12w"), Ok(Duration::weeks(12)));
+        assert_eq!(parse_age("1y"), Ok(Duration::days(365)));
+        for age in ["", "d", "3", "3é", "é", "0d", "-1d", "+-1d", "1.5w", "1m"] {
+            assert!(parse_age(age).is_err(), "{:?}", age);
+        }
+        for age in ["99999999999999d", "99999999999999w", "9223372036854775807y"] {
+            assert!(
+                parse_age(age).unwrap_err().contains("too long"),
+                "{:?}",
+                age
+            );
+        }
+        // Ages that parse but reach back before the earliest date chrono
+        // knows fail instead of purging.
+        let purge = Purge {
+            older_than: parse_age("99999999999d").unwrap(),
+        };
+        let ds = maint::DataStore::open(":memory:").unwrap();
+        let error = purge.run(&ds).unwrap_err();
+        assert_eq!(error.to_string(), "--older-than goes back too far");
+    }
+}
@@ -0,0 +1,63 @@
+use clap::Args;
+use maint::{AuditEntry, Store};
+
+#[derive(Args)]
+pub struct Cmd {
+    /// Number of operations to revert, newest first
+    #[arg(long, default_value = "1")]
+    pub steps: u32,
+}
+
+impl Cmd {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let reverted = ds.undo(self.steps)?;
+        if reverted.is_empty() {
+            println!("Nothing to undo");
+        }
+        for entry in &reverted {
+            println!("{}", describe(entry));
+        }
+
+        Ok(())
+    }
+}
+
+/// One line saying what reverting `entry` did.
+fn describe(entry: &AuditEntry) -> String {
+    let what = format!("{} {}", entry.entity, entry.entity_id);
+    let when = format!(
+        "{} by {} at {}",
+        entry.action,
+        entry.user,
+        entry.changed_at.format("%Y-%m-%d %H:%M")
+    );
+    match (&entry.old_values, &entry.new_values) {
+        (None, _) => format!("Removed {} ({})", what, when),
+        (Some(_), None) => format!("Restored {} ({})", what, when),
+        (Some(old), Some(new)) => {
+            let null = serde_json::Value::Null;
+            let mut fields = old
+                .as_object()
+                .into_iter()
+                .chain(new.as_object())
+                .flat_map(|record| record.keys())
+                .collect::<Vec<_>>();
+            fields.sort();
+            fields.dedup();
+            let changes = fields
+                .into_iter()
+                .map(|field| {
+                    (
+                        field,
+                        old.get(field).unwrap_or(&null),
+                        new.get(field).unwrap_or(&null),
+                    )
+                })
+                .filter(|(_, old, new)| old != new)
+                .map(|(field, old, new)| format!("{}: {} -> {}", field, new, old))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("Reverted {} ({}): {}", what, when, changes)
+        }
+    }
+}
@@ -1,14 +1,74 @@
+use std::path::PathBuf;
+
 use chrono::NaiveDate;
 use clap::Args;
+use maint::{GroupBy, Store};
 
-use crate::cmd::today_utc;
+use crate::cmd::{chart, config::Config, pdf, today_utc};
 
 #[derive(Args)]
 pub struct Usage {
     /// ID of the contract to show usage for
-    pub contract_id: u32,
+    #[arg(required_unless_present = "customer")]
+    pub contract_id: Option<u32>,
+
+    /// Show usage of all contracts of this customer instead
+    #[arg(long, conflicts_with = "contract_id")]
+    pub customer: Option<u32>,
 
     /// Date to show usage for (YYYY-MM-DD). Defaults to today.
-    #[arg(default_value_t = today_utc())]
-    pub date: NaiveDate,
+    #[arg(value_name = "DATE")]
+    pub on: Option<NaiveDate>,
+
+    /// Same as the positional date, for use with --customer
+    #[arg(long, conflicts_with = "on")]
+    pub date: Option<NaiveDate>,
+
+    /// Only report work from this date on, with the balance left before it
+    #[arg(long, conflicts_with = "customer")]
+    pub from: Option<NaiveDate>,
+
+    /// Same as the positional date, the last day to report on
+    #[arg(long, conflicts_with_all = ["on", "date"])]
+    pub to: Option<NaiveDate>,
+
+    /// Total the contract's usage per month, week, quarter, request or worker
+    #[arg(long, value_name = "BUCKET", conflicts_with_all = ["customer", "from"])]
+    pub group_by: Option<GroupBy>,
+
+    /// Draw a burn-down chart of the points left against an ideal linear burn
+    #[arg(long, conflicts_with_all = ["customer", "from", "group_by"])]
+    pub chart: bool,
+
+    /// Write the contract's statement, from --from or the contract start, as PDF
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["customer", "group_by", "chart"])]
+    pub pdf: Option<PathBuf>,
+}
+
+impl Usage {
+    pub fn run(&self, ds: &dyn Store) -> Result<(), Box<dyn std::error::Error>> {
+        let date = self.on.or(self.date).or(self.to).unwrap_or_else(today_utc);
+        if let (Some(contract_id), Some(path)) = (self.contract_id, &self.pdf) {
+            let contract = ds.get_contract(contract_id)?;
+            let customer = ds.get_customer(contract.customer_id)?;
+            let from = self.from.unwrap_or(contract.start_date);
+            let statement = ds.usage_between(contract_id, from, date)?;
+            return pdf::statement(path, &Config::load()?, &customer.name, &statement);
+        }
+
+        match (self.contract_id, self.customer) {
+            (Some(contract_id), _) => match (self.from, self.group_by) {
+                (Some(from), _) => println!("{}", ds.usage_between(contract_id, from, date)?),
+                (None, Some(by)) => println!("{}", ds.usage(contract_id, date)?.grouped(by)),
+                (None, None) if self.chart => {
+                    print!("{}", chart::burn_down(&ds.usage(contract_id, date)?, date))
+                }
+                (None, None) => println!("{}", ds.usage(contract_id, date)?),
+            },
+            (None, Some(customer_id)) => println!("{}", ds.customer_usage(customer_id, date)?),
+            (None, None) => unreachable!("clap requires one of them"),
+        }
+
+        Ok(())
+    }
 }
@@ -0,0 +1,481 @@
+//! Plain-text storage: every record is a YAML file in a directory tree that can
+//! be kept in git. The tree is loaded into an in-memory SQLite database, so the
+//! store behaves exactly like a database file, and written back by `flush`.
+//!
+//! ```text
+//! customer/<id>.yaml          contract/<id>.yaml
+//! request/<id>.yaml           work/<id>.yaml
+//! trash/<entity>/<id>.yaml    records in the trash, with their deletion time
+//! note/<id>.yaml              attachment/<id>.yaml, blob/<sha256>.bin
+//! audit/<id>.yaml             change history
+//! sequence.yaml               the last id handed out for each kind of record
+//! ```
+//!
+//! Ids are never handed out twice, not even those of purged records, whose
+//! change history is still in `audit/`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, named_params};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Attachment, AuditEntry, Contract, Customer, DataStore, Entity, Error, Request, RequestNote,
+    Work,
+};
+
+/// Where a `DataStore` opened with `open_dir` writes its records back to.
+pub(crate) struct Dir {
+    root: PathBuf,
+    /// `Connection::total_changes` once the tree was loaded.
+    loaded_changes: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Trashed<T> {
+    deleted_at: DateTime<Utc>,
+    record: T,
+}
+
+/// A record with a directory of its own, which can also be in the trash.
+trait Record: Serialize + DeserializeOwned {
+    const TABLE: &'static str;
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self>;
+
+    fn insert(
+        &self,
+        conn: &Connection,
+        id: u32,
+        deleted_at: Option<String>,
+    ) -> rusqlite::Result<()>;
+}
+
+impl Record for Customer {
+    const TABLE: &'static str = "customer";
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Customer::try_from(row)
+    }
+
+    fn insert(
+        &self,
+        conn: &Connection,
+        id: u32,
+        deleted_at: Option<String>,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            "INSERT INTO customer (id, name, deleted_at) VALUES (:id, :name, :deleted_at)",
+            named_params! {":id": id, ":name": self.name, ":deleted_at": deleted_at},
+        )?;
+        Ok(())
+    }
+}
+
+impl Record for Contract {
+    const TABLE: &'static str = "contract";
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Contract::try_from(row)
+    }
+
+    fn insert(
+        &self,
+        conn: &Connection,
+        id: u32,
+        deleted_at: Option<String>,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            "INSERT INTO contract (id, customer_id, start_date, end_date, total_points, deleted_at)
+                VALUES (:id, :customer_id, :start_date, :end_date, :total_points, :deleted_at)",
+            named_params! {
+                ":id": id,
+                ":customer_id": self.customer_id,
+                ":start_date": self.start_date.to_string(),
+                ":end_date": self.end_date.to_string(),
+                ":total_points": self.total_points,
+                ":deleted_at": deleted_at,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+impl Record for Request {
+    const TABLE: &'static str = "request";
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Request::try_from(row)
+    }
+
+    fn insert(
+        &self,
+        conn: &Connection,
+        id: u32,
+        deleted_at: Option<String>,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            "INSERT INTO request (id, contract_id, description, request_date, deleted_at)
+                VALUES (:id, :contract_id, :description, :request_date, :deleted_at)",
+            named_params! {
+                ":id": id,
+                ":contract_id": self.contract_id,
+                ":description": self.description,
+                ":request_date": self.request_date.to_string(),
+                ":deleted_at": deleted_at,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+impl Record for Work {
+    const TABLE: &'static str = "work";
+
+    fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+        Work::try_from(row)
+    }
+
+    fn insert(
+        &self,
+        conn: &Connection,
+        id: u32,
+        deleted_at: Option<String>,
+    ) -> rusqlite::Result<()> {
+        conn.execute(
+            "INSERT INTO work
+                (id, request_id, worker, description, points_used, work_date, deleted_at)
+                VALUES
+                (:id, :request_id, :worker, :description, :points_used, :work_date, :deleted_at)",
+            named_params! {
+                ":id": id,
+                ":request_id": self.request_id,
+                ":worker": self.worker,
+                ":description": self.description,
+                ":points_used": self.points_used,
+                ":work_date": self.work_date.to_string(),
+                ":deleted_at": deleted_at,
+            },
+        )?;
+        Ok(())
+    }
+}
+
+/// File contents by file name, for one directory.
+type Files = BTreeMap<String, Vec<u8>>;
+
+fn format_error(path: &Path, e: impl std::fmt::Display) -> Error {
+    Error::Format {
+        path: path.to_path_buf(),
+        message: e.to_string(),
+    }
+}
+
+fn yaml<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_yaml::to_string(value).unwrap().into_bytes()
+}
+
+/// The `<id>.yaml` files in `dir`, parsed and sorted by id. A missing
+/// directory has none.
+fn read_records<T: DeserializeOwned>(dir: &Path) -> Result<Vec<(u32, T)>, Error> {
+    let mut records = Vec::new();
+    for (path, name) in list(dir, "yaml")? {
+        let id = name
+            .parse()
+            .map_err(|_| format_error(&path, "file name is not a record id"))?;
+        let text = fs::read_to_string(&path)?;
+        let record = serde_yaml::from_str(&text).map_err(|e| format_error(&path, e))?;
+        records.push((id, record));
+    }
+    records.sort_by_key(|(id, _)| *id);
+    Ok(records)
+}
+
+/// Paths and stems of the files in `dir` with the given extension.
+fn list(dir: &Path, extension: &str) -> Result<Vec<(PathBuf, String)>, Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == extension)
+            && let Some(stem) = path.file_stem().and_then(|s| s.to_str())
+        {
+            let stem = stem.to_string();
+            files.push((path, stem));
+        }
+    }
+    Ok(files)
+}
+
+/// Makes the files in `dir` with the given extension exactly `files`, leaving
+/// unchanged files untouched so only real changes show up in git.
+fn write_files(dir: &Path, extension: &str, files: &Files) -> Result<(), Error> {
+    for (path, name) in list(dir, extension)? {
+        if !files.contains_key(&name) {
+            fs::remove_file(path)?;
+        }
+    }
+    if !files.is_empty() {
+        fs::create_dir_all(dir)?;
+    }
+    for (name, contents) in files {
+        let path = dir.join(format!("{}.{}", name, extension));
+        if fs::read(&path).ok().as_ref() != Some(contents) {
+            fs::write(&path, contents)?;
+        }
+    }
+    Ok(())
+}
+
+impl DataStore {
+    /// Opens the record tree under `root`, which need not exist yet. Changes
+    /// are kept in memory until `flush`.
+    pub fn open_dir<P: AsRef<Path>>(root: P) -> Result<Self, Error> {
+        let root = root.as_ref().to_path_buf();
+        let conn = Connection::open_in_memory()?;
+        Self::init(&conn)?;
+        let ds = DataStore::new(conn);
+        ds.atomic(|| {
+            ds.load::<Customer>(&root)?;
+            ds.load::<Contract>(&root)?;
+            ds.load::<Request>(&root)?;
+            ds.load::<Work>(&root)?;
+            ds.load_rest(&root)?;
+            ds.load_sequence(&root)
+        })?;
+
+        let loaded_changes = ds.conn.total_changes();
+        Ok(DataStore {
+            dir: Some(Dir {
+                root,
+                loaded_changes,
+            }),
+            ..ds
+        })
+    }
+
+    /// Writes the records back to the tree this store was opened from, if
+    /// anything changed. Does nothing for database files.
+    pub fn flush(&self) -> Result<(), Error> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        if self.conn.total_changes() == dir.loaded_changes {
+            return Ok(());
+        }
+
+        self.dump::<Customer>(&dir.root)?;
+        self.dump::<Contract>(&dir.root)?;
+        self.dump::<Request>(&dir.root)?;
+        self.dump::<Work>(&dir.root)?;
+        self.dump_rest(&dir.root)?;
+        self.dump_sequence(&dir.root)
+    }
+
+    fn load<T: Record>(&self, root: &Path) -> Result<(), Error> {
+        let dir = root.join(T::TABLE);
+        for (id, record) in read_records::<T>(&dir)? {
+            self.load_record(&dir, id, &record, None)?;
+        }
+        let dir = root.join("trash").join(T::TABLE);
+        for (id, trashed) in read_records::<Trashed<T>>(&dir)? {
+            let deleted_at = Some(trashed.deleted_at.to_rfc3339());
+            self.load_record(&dir, id, &trashed.record, deleted_at)?;
+        }
+        Ok(())
+    }
+
+    fn load_record<T: Record>(
+        &self,
+        dir: &Path,
+        id: u32,
+        record: &T,
+        deleted_at: Option<String>,
+    ) -> Result<(), Error> {
+        let path = dir.join(format!("{}.yaml", id));
+        record
+            .insert(&self.conn, id, deleted_at)
+            .map_err(|e| format_error(&path, e))
+    }
+
+    fn load_rest(&self, root: &Path) -> Result<(), Error> {
+        for (_, note) in read_records::<RequestNote>(&root.join("note"))? {
+            self.conn.execute(
+                "INSERT INTO request_note (id, request_id, author, created_at, body)
+                    VALUES (:id, :request_id, :author, :created_at, :body)",
+                named_params! {
+                    ":id": note.id,
+                    ":request_id": note.request_id,
+                    ":author": note.author,
+                    ":created_at": note.created_at.to_rfc3339(),
+                    ":body": note.body,
+                },
+            )?;
+        }
+        for (path, sha256) in list(&root.join("blob"), "bin")? {
+            self.conn.execute(
+                "INSERT INTO attachment_blob (sha256, data) VALUES (:sha256, :data)",
+                named_params! {":sha256": sha256, ":data": fs::read(&path)?},
+            )?;
+        }
+        for (_, a) in read_records::<Attachment>(&root.join("attachment"))? {
+            self.conn.execute(
+                "INSERT INTO attachment (id, request_id, work_id, filename, size, sha256, created_at)
+                    VALUES (:id, :request_id, :work_id, :filename, :size, :sha256, :created_at)",
+                named_params! {
+                    ":id": a.id,
+                    ":request_id": a.request_id,
+                    ":work_id": a.work_id,
+                    ":filename": a.filename,
+                    ":size": a.size,
+                    ":sha256": a.sha256,
+                    ":created_at": a.created_at.to_rfc3339(),
+                },
+            )?;
+        }
+        for (_, e) in read_records::<AuditEntry>(&root.join("audit"))? {
+            self.conn.execute(
+                "INSERT INTO audit
+                    (id, entity, entity_id, action, old_values, new_values, user, changed_at, reverts,
+                        operation)
+                    VALUES
+                    (:id, :entity, :entity_id, :action, :old_values, :new_values, :user,
+                        :changed_at, :reverts, :operation)",
+                named_params! {
+                    ":id": e.id,
+                    ":entity": e.entity,
+                    ":entity_id": e.entity_id,
+                    ":action": e.action,
+                    ":old_values": e.old_values.map(|v| v.to_string()),
+                    ":new_values": e.new_values.map(|v| v.to_string()),
+                    ":user": e.user,
+                    ":changed_at": e.changed_at.to_rfc3339(),
+                    ":reverts": e.reverts,
+                    ":operation": e.operation,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Raises SQLite's id sequences to the last ids handed out before, from
+    /// `sequence.yaml` and, for trees written without one, the change history.
+    fn load_sequence(&self, root: &Path) -> Result<(), Error> {
+        let path = root.join("sequence.yaml");
+        let mut last: BTreeMap<String, u32> = match fs::read_to_string(&path) {
+            Ok(text) => serde_yaml::from_str(&text).map_err(|e| format_error(&path, e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut stmt = self
+            .conn
+            .prepare("select entity, max(entity_id) from audit group by entity")?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, u32>(1)?)))?;
+        for row in rows {
+            let (entity, id) = row?;
+            if let Ok(entity) = entity.parse::<Entity>() {
+                let seq = last.entry(entity.table().to_string()).or_default();
+                *seq = (*seq).max(id);
+            }
+        }
+
+        for (table, seq) in last {
+            let params = named_params! {":name": table, ":seq": seq};
+            let updated = self.conn.execute(
+                "update sqlite_sequence set seq = max(seq, :seq) where name = :name",
+                params,
+            )?;
+            if updated == 0 {
+                self.conn.execute(
+                    "insert into sqlite_sequence (name, seq) values (:name, :seq)",
+                    params,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn dump_sequence(&self, root: &Path) -> Result<(), Error> {
+        let mut stmt = self
+            .conn
+            .prepare("select name, seq from sqlite_sequence order by name")?;
+        let last = stmt
+            .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, u32>(1)?)))?
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        let path = root.join("sequence.yaml");
+        let contents = yaml(&last);
+        if fs::read(&path).ok().as_ref() != Some(&contents) {
+            fs::create_dir_all(root)?;
+            fs::write(&path, contents)?;
+        }
+        Ok(())
+    }
+
+    fn dump<T: Record>(&self, root: &Path) -> Result<(), Error> {
+        let mut live = Files::new();
+        let mut trashed = Files::new();
+        let mut stmt = self.conn.prepare(&format!("select * from {}", T::TABLE))?;
+        let rows = stmt.query_map([], |r| {
+            Ok((
+                r.get::<_, u32>("id")?,
+                T::from_row(r)?,
+                r.get::<_, Option<String>>("deleted_at")?,
+            ))
+        })?;
+        for row in rows {
+            let (id, record, deleted_at) = row?;
+            match deleted_at {
+                None => live.insert(id.to_string(), yaml(&record)),
+                Some(deleted_at) => trashed.insert(
+                    id.to_string(),
+                    yaml(&Trashed {
+                        deleted_at: DateTime::parse_from_rfc3339(&deleted_at).unwrap().to_utc(),
+                        record,
+                    }),
+                ),
+            };
+        }
+        write_files(&root.join(T::TABLE), "yaml", &live)?;
+        write_files(&root.join("trash").join(T::TABLE), "yaml", &trashed)
+    }
+
+    fn dump_rest(&self, root: &Path) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare("select * from request_note")?;
+        let notes = stmt
+            .query_map([], |r| RequestNote::try_from(r))?
+            .map(|n| n.map(|n| (n.id.to_string(), yaml(&n))))
+            .collect::<Result<Files, _>>()?;
+        write_files(&root.join("note"), "yaml", &notes)?;
+
+        let mut stmt = self.conn.prepare("select * from attachment")?;
+        let attachments = stmt
+            .query_map([], |r| Attachment::try_from(r))?
+            .map(|a| a.map(|a| (a.id.to_string(), yaml(&a))))
+            .collect::<Result<Files, _>>()?;
+        write_files(&root.join("attachment"), "yaml", &attachments)?;
+
+        let mut stmt = self
+            .conn
+            .prepare("select sha256, data from attachment_blob")?;
+        let blobs = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect::<Result<Files, _>>()?;
+        write_files(&root.join("blob"), "bin", &blobs)?;
+
+        let mut stmt = self.conn.prepare("select * from audit")?;
+        let audit = stmt
+            .query_map([], |r| AuditEntry::try_from(r))?
+            .map(|e| e.map(|e| (e.id.to_string(), yaml(&e))))
+            .collect::<Result<Files, _>>()?;
+        write_files(&root.join("audit"), "yaml", &audit)
+    }
+}
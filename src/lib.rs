@@ -1,19 +1,36 @@
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::fmt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
-use chrono::NaiveDate;
-use rusqlite::{Connection, named_params};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, named_params};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-#[derive(Debug, Serialize, Deserialize)]
+mod dir;
+mod memory;
+#[cfg(feature = "postgres")]
+mod postgres;
+mod store;
+
+pub use memory::MemoryStore;
+#[cfg(feature = "postgres")]
+pub use postgres::PgStore;
+pub use store::Store;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Customer {
     #[serde(skip)]
     pub id: u32,
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Contract {
     #[serde(skip)]
     pub id: u32,
@@ -23,7 +40,37 @@ pub struct Contract {
     pub total_points: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Contract {
+    /// Whether `self` is for another customer or period than `old`, so that
+    /// overlaps with other contracts need checking again.
+    pub(crate) fn moved(&self, old: &Contract) -> bool {
+        self.customer_id != old.customer_id
+            || self.start_date != old.start_date
+            || self.end_date != old.end_date
+    }
+}
+
+/// Whether a contract may overlap other contracts of the same customer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlap {
+    Reject,
+    Allow,
+}
+
+/// How SQLite keeps a database file intact while writing to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Journal {
+    /// A rollback journal next to the file while writing, which works wherever
+    /// the file is, network filesystems included.
+    Rollback,
+    /// A write-ahead log, which lets readers carry on while someone writes.
+    /// It needs memory shared by everyone using the file, so they must all be
+    /// on one machine.
+    Wal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Request {
     #[serde(skip)]
     pub id: u32,
@@ -32,7 +79,8 @@ pub struct Request {
     pub request_date: chrono::NaiveDate,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Work {
     #[serde(skip)]
     pub id: u32,
@@ -43,627 +91,4705 @@ pub struct Work {
     pub work_date: chrono::NaiveDate,
 }
 
-#[derive(Debug, Serialize)]
-pub struct CumulativeUsage {
-    pub request_date: NaiveDate,
-    pub request_description: String,
-    pub worker: String,
-    pub work_date: NaiveDate,
-    pub work_description: String,
-    pub points_used: u32,
-    pub cumulative_points_used: u32,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RequestNote {
+    pub id: u32,
+    pub request_id: u32,
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+    pub body: String,
 }
 
-impl TryFrom<&rusqlite::Row<'_>> for CumulativeUsage {
+impl TryFrom<&rusqlite::Row<'_>> for RequestNote {
     type Error = rusqlite::Error;
 
     fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
         Ok(Self {
-            request_date: NaiveDate::from_str(value.get::<_, String>(0)?.as_str()).unwrap(),
-            request_description: value.get(1)?,
-            worker: value.get(2)?,
-            work_date: NaiveDate::from_str(value.get::<_, String>(3)?.as_str()).unwrap(),
-            work_description: value.get(4)?,
-            points_used: value.get(5)?,
-            cumulative_points_used: value.get(6)?,
+            id: value.get(0)?,
+            request_id: value.get(1)?,
+            author: value.get(2)?,
+            created_at: DateTime::parse_from_rfc3339(value.get::<_, String>(3)?.as_str())
+                .unwrap()
+                .to_utc(),
+            body: value.get(4)?,
         })
     }
 }
 
-impl fmt::Display for CumulativeUsage {
+impl fmt::Display for RequestNote {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+        writeln!(
+            f,
+            "--- {} ({})",
+            self.author,
+            self.created_at.format("%Y-%m-%d %H:%M")
+        )?;
+        write!(f, "{}", self.body.trim_end())
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct ContractUsage {
-    pub start_date: NaiveDate,
-    pub end_date: NaiveDate,
-    pub total_points: u32,
-    pub cumulative_usage: Vec<CumulativeUsage>,
-}
-
-impl fmt::Display for ContractUsage {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", serde_yaml::to_string(self).unwrap())
-    }
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: u32,
+    pub request_id: u32,
+    pub work_id: Option<u32>,
+    pub filename: String,
+    pub size: u64,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
 }
 
-impl TryFrom<&rusqlite::Row<'_>> for Customer {
+impl TryFrom<&rusqlite::Row<'_>> for Attachment {
     type Error = rusqlite::Error;
 
     fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
         Ok(Self {
             id: value.get(0)?,
-            name: value.get(1)?,
+            request_id: value.get(1)?,
+            work_id: value.get(2)?,
+            filename: value.get(3)?,
+            size: value.get(4)?,
+            sha256: value.get(5)?,
+            created_at: DateTime::parse_from_rfc3339(value.get::<_, String>(6)?.as_str())
+                .unwrap()
+                .to_utc(),
         })
     }
 }
 
-impl TryFrom<&rusqlite::Row<'_>> for Contract {
-    type Error = rusqlite::Error;
+impl fmt::Display for Attachment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
 
-    fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
-        Ok(Self {
-            id: value.get(0)?,
-            customer_id: value.get(1)?,
-            start_date: chrono::NaiveDate::from_str(value.get::<_, String>(2)?.as_str()).unwrap(),
-            end_date: chrono::NaiveDate::from_str(value.get::<_, String>(3)?.as_str()).unwrap(),
-            total_points: value.get(4)?,
-        })
+/// The kinds of records kept in a `DataStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entity {
+    Customer,
+    Contract,
+    Request,
+    Work,
+    Note,
+    Attachment,
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Entity::Customer => "customer",
+            Entity::Contract => "contract",
+            Entity::Request => "request",
+            Entity::Work => "work",
+            Entity::Note => "note",
+            Entity::Attachment => "attachment",
+        };
+        write!(f, "{}", name)
     }
 }
 
-impl TryFrom<&rusqlite::Row<'_>> for Request {
+impl Entity {
+    /// Whether records of this kind go to the trash when deleted.
+    pub fn is_trashable(&self) -> bool {
+        matches!(
+            self,
+            Entity::Customer | Entity::Contract | Entity::Request | Entity::Work
+        )
+    }
+
+    fn table(&self) -> &'static str {
+        match self {
+            Entity::Customer => "customer",
+            Entity::Contract => "contract",
+            Entity::Request => "request",
+            Entity::Work => "work",
+            Entity::Note => "request_note",
+            Entity::Attachment => "attachment",
+        }
+    }
+}
+
+impl FromStr for Entity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "customer" => Ok(Entity::Customer),
+            "contract" => Ok(Entity::Contract),
+            "request" => Ok(Entity::Request),
+            "work" => Ok(Entity::Work),
+            "note" => Ok(Entity::Note),
+            "attachment" => Ok(Entity::Attachment),
+            _ => Err(format!("unknown entity: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: u32,
+    pub entity: String,
+    pub entity_id: u32,
+    pub action: String,
+    pub old_values: Option<serde_json::Value>,
+    pub new_values: Option<serde_json::Value>,
+    pub user: String,
+    pub changed_at: DateTime<Utc>,
+    pub reverts: Option<u32>,
+    pub operation: Option<u32>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for AuditEntry {
     type Error = rusqlite::Error;
 
     fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        let json = |idx: usize| -> Result<Option<serde_json::Value>, rusqlite::Error> {
+            Ok(value
+                .get::<_, Option<String>>(idx)?
+                .map(|s| serde_json::from_str(&s).unwrap()))
+        };
         Ok(Self {
             id: value.get(0)?,
-            contract_id: value.get(1)?,
-            description: value.get(2)?,
-            request_date: chrono::NaiveDate::from_str(value.get::<_, String>(3)?.as_str()).unwrap(),
+            entity: value.get(1)?,
+            entity_id: value.get(2)?,
+            action: value.get(3)?,
+            old_values: json(4)?,
+            new_values: json(5)?,
+            user: value.get(6)?,
+            changed_at: DateTime::parse_from_rfc3339(value.get::<_, String>(7)?.as_str())
+                .unwrap()
+                .to_utc(),
+            reverts: value.get(8)?,
+            operation: value.get(9)?,
         })
     }
 }
 
-impl TryFrom<&rusqlite::Row<'_>> for Work {
+impl fmt::Display for AuditEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+/// Name of the person running maint, recorded in the audit log.
+pub fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrashEntry {
+    pub entity: String,
+    pub id: u32,
+    pub summary: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for TrashEntry {
     type Error = rusqlite::Error;
 
     fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
         Ok(Self {
-            id: value.get(0)?,
-            request_id: value.get(1)?,
-            worker: value.get(2)?,
-            description: value.get(3)?,
-            points_used: value.get(4)?,
-            work_date: chrono::NaiveDate::from_str(value.get::<_, String>(5)?.as_str()).unwrap(),
+            entity: value.get(0)?,
+            id: value.get(1)?,
+            summary: value.get(2)?,
+            deleted_at: DateTime::parse_from_rfc3339(value.get::<_, String>(3)?.as_str())
+                .unwrap()
+                .to_utc(),
         })
     }
 }
 
-impl fmt::Display for Customer {
+impl fmt::Display for TrashEntry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
-impl fmt::Display for Contract {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+/// Two contracts of one customer that are both in force from `from` to `to`.
+#[derive(Debug, Serialize)]
+pub struct ContractOverlap {
+    pub customer_id: u32,
+    pub customer: Option<String>,
+    pub first_contract_id: u32,
+    pub second_contract_id: u32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnaccountedWork {
+    pub work_id: u32,
+    pub request_id: u32,
+    pub contract_id: u32,
+    pub customer: Option<String>,
+    pub contract_start_date: NaiveDate,
+    pub contract_end_date: NaiveDate,
+    pub request_date: NaiveDate,
+    pub work_date: NaiveDate,
+    pub worker: String,
+    pub description: String,
+    pub points_used: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CumulativeUsage {
+    pub request_date: NaiveDate,
+    pub request_description: String,
+    pub worker: String,
+    pub work_date: NaiveDate,
+    pub work_description: String,
+    pub points_used: u32,
+    pub cumulative_points_used: u32,
+    #[serde(skip)]
+    pub request_id: u32,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for CumulativeUsage {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            request_date: NaiveDate::from_str(value.get::<_, String>(0)?.as_str()).unwrap(),
+            request_description: value.get(1)?,
+            worker: value.get(2)?,
+            work_date: NaiveDate::from_str(value.get::<_, String>(3)?.as_str()).unwrap(),
+            work_description: value.get(4)?,
+            points_used: value.get(5)?,
+            cumulative_points_used: value.get(6)?,
+            request_id: value.get(7)?,
+        })
     }
 }
 
-impl fmt::Display for Request {
+impl fmt::Display for CumulativeUsage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
-impl fmt::Display for Work {
+#[derive(Debug, Serialize)]
+pub struct ContractUsage {
+    #[serde(skip)]
+    pub contract_id: u32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub total_points: u32,
+    pub cumulative_usage: Vec<CumulativeUsage>,
+}
+
+impl fmt::Display for ContractUsage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
-impl FromStr for Customer {
-    type Err = serde_yaml::Error;
+impl ContractUsage {
+    /// Points left at the end of each day work was logged, by work date.
+    pub fn remaining_by_date(&self) -> Vec<(NaiveDate, i64)> {
+        let mut work: Vec<_> = self
+            .cumulative_usage
+            .iter()
+            .map(|u| (u.work_date, u.points_used))
+            .collect();
+        work.sort();
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_yaml::from_str(s)
+        let mut remaining = self.total_points as i64;
+        let mut result: Vec<(NaiveDate, i64)> = Vec::new();
+        for (date, points) in work {
+            remaining -= points as i64;
+            match result.last_mut() {
+                Some(last) if last.0 == date => last.1 = remaining,
+                _ => result.push((date, remaining)),
+            }
+        }
+        result
     }
-}
 
-impl FromStr for Contract {
-    type Err = serde_yaml::Error;
+    /// Totals per bucket in bucket order, with running cumulative totals.
+    /// Time buckets go by work date, request buckets by request date and
+    /// worker buckets by name.
+    pub fn grouped(&self, by: GroupBy) -> GroupedUsage {
+        let mut buckets: Vec<UsageBucket> = Vec::new();
+        let mut usage: Vec<&CumulativeUsage> = self.cumulative_usage.iter().collect();
+        if matches!(by, GroupBy::Request) {
+            // Work on requests of the same day may be interleaved.
+            usage.sort_by_key(|u| (u.request_date, u.request_id));
+        }
+        let mut keyed: Vec<(String, Option<String>, u32)> = usage
+            .into_iter()
+            .map(|u| {
+                let d = u.work_date;
+                match by {
+                    GroupBy::Month => (
+                        format!("{}-{:02}", d.year(), d.month()),
+                        None,
+                        u.points_used,
+                    ),
+                    GroupBy::Week => {
+                        let w = d.iso_week();
+                        (
+                            format!("{}-W{:02}", w.year(), w.week()),
+                            None,
+                            u.points_used,
+                        )
+                    }
+                    GroupBy::Quarter => (
+                        format!("{}-Q{}", d.year(), d.month0() / 3 + 1),
+                        None,
+                        u.points_used,
+                    ),
+                    GroupBy::Request => (
+                        u.request_id.to_string(),
+                        Some(u.request_description.clone()),
+                        u.points_used,
+                    ),
+                    GroupBy::Worker => (u.worker.clone(), None, u.points_used),
+                }
+            })
+            .collect();
+        if !matches!(by, GroupBy::Request) {
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+        }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_yaml::from_str(s)
+        let mut cumulative = 0;
+        for (bucket, description, points) in keyed {
+            cumulative += points;
+            match buckets.last_mut() {
+                Some(last) if last.bucket == bucket => {
+                    last.points_used += points;
+                    last.cumulative_points_used = cumulative;
+                }
+                _ => buckets.push(UsageBucket {
+                    bucket,
+                    description,
+                    points_used: points,
+                    cumulative_points_used: cumulative,
+                }),
+            }
+        }
+
+        GroupedUsage {
+            contract_id: self.contract_id,
+            start_date: self.start_date,
+            end_date: self.end_date,
+            total_points: self.total_points,
+            group_by: by,
+            buckets,
+        }
     }
 }
 
-impl FromStr for Request {
-    type Err = serde_yaml::Error;
+/// Usage of a contract within a window of work dates, for statements.
+#[derive(Debug, Serialize)]
+pub struct UsageStatement {
+    #[serde(skip)]
+    pub contract_id: u32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub total_points: u32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub opening_points_used: u32,
+    pub opening_balance: i64,
+    pub points_used: u32,
+    pub closing_balance: i64,
+    pub cumulative_usage: Vec<CumulativeUsage>,
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_yaml::from_str(s)
+impl fmt::Display for UsageStatement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
-impl FromStr for Work {
-    type Err = serde_yaml::Error;
+/// How `ContractUsage::grouped` buckets work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Month,
+    Week,
+    Quarter,
+    Request,
+    Worker,
+}
+
+impl FromStr for GroupBy {
+    type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_yaml::from_str(s)
+        match s {
+            "month" => Ok(GroupBy::Month),
+            "week" => Ok(GroupBy::Week),
+            "quarter" => Ok(GroupBy::Quarter),
+            "request" => Ok(GroupBy::Request),
+            "worker" => Ok(GroupBy::Worker),
+            _ => Err(format!(
+                "unknown grouping: {} (expected month, week, quarter, request or worker)",
+                s
+            )),
+        }
     }
 }
 
-#[derive(Debug)]
-pub enum Error {
-    RusqliteError(rusqlite::Error),
+#[derive(Debug, Serialize)]
+pub struct UsageBucket {
+    pub bucket: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub points_used: u32,
+    pub cumulative_points_used: u32,
 }
 
-impl From<rusqlite::Error> for Error {
-    fn from(value: rusqlite::Error) -> Self {
-        Self::RusqliteError(value)
-    }
+#[derive(Debug, Serialize)]
+pub struct GroupedUsage {
+    #[serde(skip)]
+    pub contract_id: u32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub total_points: u32,
+    pub group_by: GroupBy,
+    pub buckets: Vec<UsageBucket>,
 }
 
-impl std::error::Error for Error {}
-impl fmt::Display for Error {
+impl fmt::Display for GroupedUsage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "{:?}", self)
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
 }
 
-pub struct DataStore {
-    conn: Connection,
+/// Where a request stands, derived from the work logged on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestStatus {
+    /// No work logged yet.
+    Open,
+    /// Work logged and counted against the contract.
+    Worked,
+    /// Dated outside the contract period, so usage ignores its work.
+    Unaccounted,
 }
 
-impl DataStore {
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
-        let is_new_db = !path.as_ref().exists();
-        let conn = Connection::open(path)?;
-
-        if is_new_db {
-            Self::init(&conn)?;
-        }
-
-        Ok(DataStore { conn })
+impl fmt::Display for RequestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RequestStatus::Open => "open",
+            RequestStatus::Worked => "worked",
+            RequestStatus::Unaccounted => "unaccounted",
+        };
+        write!(f, "{}", name)
     }
+}
 
-    pub fn add_customer(&self, name: &str) -> Result<usize, Error> {
-        let rows = self.conn.execute(
-            "INSERT INTO customer (name) VALUES (:name)",
-            &[(":name", name)],
-        )?;
+#[derive(Debug, Serialize)]
+pub struct RequestReport {
+    pub id: u32,
+    pub request_date: NaiveDate,
+    pub description: String,
+    pub status: RequestStatus,
+    pub points_used: u32,
+    pub work: Vec<Work>,
+}
+
+/// Everything a customer-facing report on one contract shows.
+#[derive(Debug, Serialize)]
+pub struct ContractReport {
+    pub date: NaiveDate,
+    pub customer_id: u32,
+    pub customer: String,
+    pub contract: ContractSummary,
+    pub requests: Vec<RequestReport>,
+    #[serde(skip)]
+    pub usage: ContractUsage,
+}
 
-        Ok(rows)
+impl fmt::Display for ContractReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
+}
 
-    pub fn add_contract(
-        &self,
-        customer_id: u32,
-        start_date: &NaiveDate,
-        end_date: &NaiveDate,
-        total_points: u32,
-    ) -> Result<usize, Error> {
-        let rows = self.conn.execute(
-            "INSERT INTO contract (customer_id, start_date, end_date, total_points)
-                VALUES (:customer_id, :start_date, :end_date, :total_points)",
-            &[
-                (":customer_id", &customer_id.to_string()),
-                (":start_date", &start_date.to_string()),
-                (":end_date", &end_date.to_string()),
-                (":total_points", &total_points.to_string()),
-            ],
-        )?;
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub entity: String,
+    pub id: u32,
+    pub customer_id: Option<u32>,
+    pub customer: Option<String>,
+    pub snippet: String,
+}
 
-        Ok(rows)
-    }
+impl TryFrom<&rusqlite::Row<'_>> for SearchHit {
+    type Error = rusqlite::Error;
 
-    pub fn add_request(
-        &self,
-        contract_id: u32,
-        description: &str,
-        request_date: &NaiveDate,
-    ) -> Result<usize, Error> {
-        let rows = self.conn.execute(
-            "INSERT INTO request (contract_id, description, request_date)
-                VALUES (:contract_id, :description, :request_date)",
-            &[
-                (":contract_id", contract_id.to_string().as_str()),
-                (":description", description),
-                (":request_date", request_date.to_string().as_str()),
-            ],
-        )?;
+    fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            entity: value.get(0)?,
+            id: value.get(1)?,
+            customer_id: value.get(2)?,
+            customer: value.get(3)?,
+            snippet: value.get(4)?,
+        })
+    }
+}
 
-        Ok(rows)
+impl fmt::Display for SearchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
+}
 
-    pub fn add_work(
-        &self,
-        request_id: u32,
-        worker: &str,
-        description: &str,
-        points_used: u32,
-        work_date: &NaiveDate,
-    ) -> Result<usize, Error> {
-        let rows = self.conn.execute(
-            "INSERT INTO work (request_id, worker, description, points_used, work_date)
-                VALUES (:request_id, :worker, :description, :points_used, :work_date)",
-            &[
-                (":request_id", request_id.to_string().as_str()),
-                (":worker", worker),
-                (":description", description),
-                (":points_used", points_used.to_string().as_str()),
-                (":work_date", work_date.to_string().as_str()),
-            ],
-        )?;
+/// Totals of one contract as of some date.
+#[derive(Debug, Serialize)]
+pub struct ContractSummary {
+    pub contract_id: u32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub total_points: u32,
+    pub points_used: u32,
+    pub points_remaining: i64,
+}
 
-        Ok(rows)
+impl From<&ContractUsage> for ContractSummary {
+    fn from(usage: &ContractUsage) -> Self {
+        let points_used = usage
+            .cumulative_usage
+            .last()
+            .map_or(0, |u| u.cumulative_points_used);
+        Self {
+            contract_id: usage.contract_id,
+            start_date: usage.start_date,
+            end_date: usage.end_date,
+            total_points: usage.total_points,
+            points_used,
+            points_remaining: usage.total_points as i64 - points_used as i64,
+        }
     }
+}
 
-    fn init(conn: &Connection) -> Result<(), Error> {
-        conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS customer (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL
-            );
-            CREATE TABLE IF NOT EXISTS contract (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                customer_id INTEGER NOT NULL,
-                start_date DATE NOT NULL,
-                end_date DATE NOT NULL,
-                total_points INTEGER NOT NULL,
-                FOREIGN KEY (customer_id) REFERENCES customer(id)
-            );
-            CREATE TABLE IF NOT EXISTS request (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                contract_id INTEGER NOT NULL,
-                description TEXT NOT NULL,
-                request_date DATE NOT NULL,
-                FOREIGN KEY (contract_id) REFERENCES contract(id)
-            );
-            CREATE TABLE IF NOT EXISTS work (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                request_id INTEGER NOT NULL,
-                worker TEXT NOT NULL,
-                description TEXT NOT NULL,
-                points_used INTEGER NOT NULL,
-                work_date DATE NOT NULL,
-                FOREIGN KEY (request_id) REFERENCES request(id)
-            );
-            ",
-        )?;
+/// Usage of all contracts of a customer, with lifetime totals.
+#[derive(Debug, Serialize)]
+pub struct CustomerUsage {
+    pub customer_id: u32,
+    pub customer: String,
+    pub contracts: Vec<ContractSummary>,
+    pub total_points: u32,
+    pub points_used: u32,
+    pub points_remaining: i64,
+}
 
-        Ok(())
+impl fmt::Display for CustomerUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
+}
 
-    pub fn list_customer(&self) -> Result<Vec<Customer>, rusqlite::Error> {
-        let query = "select * from customer";
-        let mut stmt = self.conn.prepare(query)?;
-        let rows = stmt.query_map([], |row| Customer::try_from(row))?;
-        let mut customers = Vec::new();
-        for customer_result in rows {
-            customers.push(customer_result?);
+/// How a contract's consumption compares with the time elapsed, worst last.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BurnStatus {
+    Ok,
+    Warning,
+    Critical,
+    Exhausted,
+}
+
+impl BurnStatus {
+    /// Consumption running more than 10 (25) points of percentage ahead of the
+    /// elapsed part of the period is a warning (critical).
+    fn new(burn_percent: f64, elapsed_percent: f64) -> Self {
+        if burn_percent >= 100.0 {
+            BurnStatus::Exhausted
+        } else if burn_percent >= 90.0 || burn_percent - elapsed_percent > 25.0 {
+            BurnStatus::Critical
+        } else if burn_percent - elapsed_percent > 10.0 {
+            BurnStatus::Warning
+        } else {
+            BurnStatus::Ok
         }
+    }
+}
 
-        Ok(customers)
+impl fmt::Display for BurnStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BurnStatus::Ok => "ok",
+            BurnStatus::Warning => "warning",
+            BurnStatus::Critical => "critical",
+            BurnStatus::Exhausted => "exhausted",
+        };
+        write!(f, "{}", name)
     }
+}
 
-    pub fn list_contract(&self) -> Result<Vec<Contract>, rusqlite::Error> {
-        let query = "select * from contract";
-        let mut stmt = self.conn.prepare(query)?;
-        let rows = stmt.query_map([], |row| Contract::try_from(row))?;
-        let mut contracts = Vec::new();
-        for contract_result in rows {
-            contracts.push(contract_result?);
-        }
+/// One active contract on the dashboard.
+#[derive(Debug, Serialize)]
+pub struct DashboardEntry {
+    pub contract_id: u32,
+    pub customer_id: u32,
+    pub customer: Option<String>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub days_remaining: i64,
+    pub total_points: u32,
+    pub points_used: u32,
+    pub burn_percent: f64,
+    pub elapsed_percent: f64,
+    pub status: BurnStatus,
+}
 
-        Ok(contracts)
+impl TryFrom<&rusqlite::Row<'_>> for Customer {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.get(0)?,
+            name: value.get(1)?,
+        })
     }
+}
 
-    pub fn list_request(&self) -> Result<Vec<Request>, rusqlite::Error> {
-        let query = "select * from request";
-        let mut stmt = self.conn.prepare(query)?;
-        let rows = stmt.query_map([], |row| Request::try_from(row))?;
-        let mut requests = Vec::new();
-        for request_result in rows {
-            requests.push(request_result?);
-        }
+impl TryFrom<&rusqlite::Row<'_>> for Contract {
+    type Error = rusqlite::Error;
 
-        Ok(requests)
+    fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.get(0)?,
+            customer_id: value.get(1)?,
+            start_date: chrono::NaiveDate::from_str(value.get::<_, String>(2)?.as_str()).unwrap(),
+            end_date: chrono::NaiveDate::from_str(value.get::<_, String>(3)?.as_str()).unwrap(),
+            total_points: value.get(4)?,
+        })
     }
+}
 
-    pub fn list_work(&self) -> Result<Vec<Work>, rusqlite::Error> {
-        let query = "select * from work";
-        let mut stmt = self.conn.prepare(query)?;
-        let rows = stmt.query_map([], |row| Work::try_from(row))?;
-        let mut work_entries = Vec::new();
-        for work_result in rows {
-            work_entries.push(work_result?);
-        }
+impl TryFrom<&rusqlite::Row<'_>> for Request {
+    type Error = rusqlite::Error;
 
-        Ok(work_entries)
+    fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.get(0)?,
+            contract_id: value.get(1)?,
+            description: value.get(2)?,
+            request_date: chrono::NaiveDate::from_str(value.get::<_, String>(3)?.as_str()).unwrap(),
+        })
     }
+}
 
-    pub fn get_customer(&self, id: u32) -> Result<Customer, rusqlite::Error> {
-        self.conn
-            .query_one("select * from customer where id = :id", [id], |r| {
-                Customer::try_from(r)
-            })
+impl TryFrom<&rusqlite::Row<'_>> for Work {
+    type Error = rusqlite::Error;
+
+    fn try_from(value: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.get(0)?,
+            request_id: value.get(1)?,
+            worker: value.get(2)?,
+            description: value.get(3)?,
+            points_used: value.get(4)?,
+            work_date: chrono::NaiveDate::from_str(value.get::<_, String>(5)?.as_str()).unwrap(),
+        })
     }
+}
 
-    pub fn get_contract(&self, id: u32) -> Result<Contract, rusqlite::Error> {
-        self.conn
-            .query_one("select * from contract where id = :id", [id], |r| {
-                Contract::try_from(r)
-            })
+impl fmt::Display for Customer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
+}
 
-    pub fn get_request(&self, id: u32) -> Result<Request, rusqlite::Error> {
-        self.conn
-            .query_one("select * from request where id = :id", [id], |r| {
-                Request::try_from(r)
-            })
+impl fmt::Display for Contract {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
+}
 
-    pub fn get_work(&self, id: u32) -> Result<Work, rusqlite::Error> {
-        self.conn
-            .query_one("select * from work where id = :id", [id], |r| {
-                Work::try_from(r)
-            })
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
     }
+}
 
-    pub fn save_customer(&self, entity: Customer) -> Result<usize, rusqlite::Error> {
-        let rows = self.conn.execute(
-            "UPDATE customer SET name = :name WHERE id = :id",
-            &[(":name", &entity.name), (":id", &entity.id.to_string())],
-        )?;
+impl fmt::Display for Work {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", serde_yaml::to_string(self).unwrap())
+    }
+}
+
+impl FromStr for Customer {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+impl FromStr for Contract {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+impl FromStr for Request {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+impl FromStr for Work {
+    type Err = serde_yaml::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_yaml::from_str(s)
+    }
+}
+
+/// A problem with one field of a record.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Version of the `Export` document format written by this build.
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Every live customer, contract, request and work entry with its id, for
+/// moving data between databases. Notes, attachments, history and the trash
+/// are not included.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Export {
+    pub version: u32,
+    pub customers: Vec<ExportedCustomer>,
+    pub contracts: Vec<ExportedContract>,
+    pub requests: Vec<ExportedRequest>,
+    pub work: Vec<ExportedWork>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedCustomer {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedContract {
+    pub id: u32,
+    pub customer_id: u32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub total_points: u32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedRequest {
+    pub id: u32,
+    pub contract_id: u32,
+    pub description: String,
+    pub request_date: NaiveDate,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportedWork {
+    pub id: u32,
+    pub request_id: u32,
+    pub worker: String,
+    pub description: String,
+    pub points_used: u32,
+    pub work_date: NaiveDate,
+}
+
+/// How many records `DataStore::import` saved.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ImportSummary {
+    pub customers: usize,
+    pub contracts: usize,
+    pub requests: usize,
+    pub work: usize,
+}
+
+/// A problem with one row of a batch, such as a line of an imported file.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowError {
+    /// The part of the document the row is in, such as `contracts` in an
+    /// export, with `row` counting from 1 within it.
+    pub section: Option<&'static str>,
+    pub row: u64,
+    pub message: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.section {
+            Some(section) => write!(f, "{} row {}: {}", section, self.row, self.message),
+            None => write!(f, "row {}: {}", self.row, self.message),
+        }
+    }
+}
+
+/// Checks a record on its own, without looking at related records.
+pub trait Validate {
+    /// Every problem found, empty if the record is valid.
+    fn validate(&self) -> Vec<FieldError>;
+}
+
+impl Validate for Customer {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "must not be blank"));
+        }
+        errors
+    }
+}
+
+impl Validate for Contract {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.end_date < self.start_date {
+            errors.push(FieldError::new("end_date", "must not be before start_date"));
+        }
+        if self.total_points == 0 {
+            errors.push(FieldError::new("total_points", "must be greater than zero"));
+        }
+        errors
+    }
+}
+
+impl Validate for Request {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.description.trim().is_empty() {
+            errors.push(FieldError::new("description", "must not be blank"));
+        }
+        errors
+    }
+}
+
+impl Validate for Work {
+    fn validate(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        if self.worker.trim().is_empty() {
+            errors.push(FieldError::new("worker", "must not be blank"));
+        }
+        if self.description.trim().is_empty() {
+            errors.push(FieldError::new("description", "must not be blank"));
+        }
+        errors
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    RusqliteError(rusqlite::Error),
+    #[cfg(feature = "postgres")]
+    Postgres(::postgres::Error),
+    Io(std::io::Error),
+    Format {
+        path: std::path::PathBuf,
+        message: String,
+    },
+    NotFound(Entity, u32),
+    Validation(Vec<FieldError>),
+    Rows(Vec<RowError>),
+    NotEmpty,
+    Unsupported(String),
+    Overlap(Vec<u32>),
+    NoActiveContract {
+        customer_id: u32,
+        date: NaiveDate,
+    },
+    AmbiguousContract {
+        customer_id: u32,
+        date: NaiveDate,
+        contract_ids: Vec<u32>,
+    },
+    Undo(String),
+    NotTrashable(Entity),
+    InUse {
+        entity: Entity,
+        id: u32,
+        child: Entity,
+        child_id: u32,
+    },
+    ParentDeleted {
+        entity: Entity,
+        id: u32,
+        parent: Entity,
+        parent_id: u32,
+    },
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(value: rusqlite::Error) -> Self {
+        Self::RusqliteError(value)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<::postgres::Error> for Error {
+    fn from(value: ::postgres::Error) -> Self {
+        Self::Postgres(value)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// `result`, with a missing row reported as `Error::NotFound`.
+fn found<T>(result: rusqlite::Result<T>, entity: Entity, id: u32) -> Result<T, Error> {
+    result.map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => Error::NotFound(entity, id),
+        e => e.into(),
+    })
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::RusqliteError(rusqlite::Error::QueryReturnedNoRows) => {
+                write!(f, "no such record")
+            }
+            Error::RusqliteError(e) => write!(f, "{}", e),
+            #[cfg(feature = "postgres")]
+            Error::Postgres(e) => write!(f, "{}", e),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Format { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::NotFound(entity, id) => write!(f, "no such {} {}", entity, id),
+            Error::Validation(errors) => {
+                write!(f, "invalid record:")?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+            Error::Rows(errors) => {
+                write!(f, "{} rows failed, nothing was saved:", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
+            Error::NotEmpty => write!(
+                f,
+                "the database already has records; merge into it instead of restoring"
+            ),
+            Error::Unsupported(what) => write!(f, "not supported: {}", what),
+            Error::Overlap(ids) => write!(
+                f,
+                "overlaps {} {} of the same customer",
+                if ids.len() == 1 {
+                    "contract"
+                } else {
+                    "contracts"
+                },
+                ids.iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Error::NoActiveContract { customer_id, date } => write!(
+                f,
+                "customer {} has no contract with points left covering {}",
+                customer_id, date
+            ),
+            Error::AmbiguousContract {
+                customer_id,
+                date,
+                contract_ids,
+            } => write!(
+                f,
+                "customer {} has several contracts with points left covering {}: {}",
+                customer_id,
+                date,
+                contract_ids
+                    .iter()
+                    .map(|id| id.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Error::Undo(message) => write!(f, "cannot undo: {}", message),
+            Error::NotTrashable(entity) => write!(f, "{} records cannot be deleted", entity),
+            Error::InUse {
+                entity,
+                id,
+                child,
+                child_id,
+            } => write!(
+                f,
+                "{} {} still has {} {}; delete that first",
+                entity, id, child, child_id
+            ),
+            Error::ParentDeleted {
+                entity,
+                id,
+                parent,
+                parent_id,
+            } => write!(
+                f,
+                "{} {} belongs to {} {}, which is in the trash; restore that first",
+                entity, id, parent, parent_id
+            ),
+        }
+    }
+}
+
+/// Schema migrations, applied in order. `PRAGMA user_version` records how many
+/// of them a database has already seen, so existing files are upgraded in place.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE IF NOT EXISTS customer (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS contract (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        customer_id INTEGER NOT NULL,
+        start_date DATE NOT NULL,
+        end_date DATE NOT NULL,
+        total_points INTEGER NOT NULL,
+        FOREIGN KEY (customer_id) REFERENCES customer(id)
+    );
+    CREATE TABLE IF NOT EXISTS request (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        contract_id INTEGER NOT NULL,
+        description TEXT NOT NULL,
+        request_date DATE NOT NULL,
+        FOREIGN KEY (contract_id) REFERENCES contract(id)
+    );
+    CREATE TABLE IF NOT EXISTS work (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        request_id INTEGER NOT NULL,
+        worker TEXT NOT NULL,
+        description TEXT NOT NULL,
+        points_used INTEGER NOT NULL,
+        work_date DATE NOT NULL,
+        FOREIGN KEY (request_id) REFERENCES request(id)
+    );
+    ",
+    "
+    CREATE VIRTUAL TABLE search USING fts5(
+        entity UNINDEXED,
+        entity_id UNINDEXED,
+        body,
+        tokenize = 'porter unicode61'
+    );
+    INSERT INTO search (entity, entity_id, body) SELECT 'customer', id, name FROM customer;
+    INSERT INTO search (entity, entity_id, body) SELECT 'request', id, description FROM request;
+    INSERT INTO search (entity, entity_id, body) SELECT 'work', id, description FROM work;
+
+    CREATE TRIGGER customer_search_insert AFTER INSERT ON customer BEGIN
+        INSERT INTO search (entity, entity_id, body) VALUES ('customer', new.id, new.name);
+    END;
+    CREATE TRIGGER customer_search_update AFTER UPDATE OF name ON customer BEGIN
+        UPDATE search SET body = new.name WHERE entity = 'customer' AND entity_id = old.id;
+    END;
+    CREATE TRIGGER customer_search_delete AFTER DELETE ON customer BEGIN
+        DELETE FROM search WHERE entity = 'customer' AND entity_id = old.id;
+    END;
+
+    CREATE TRIGGER request_search_insert AFTER INSERT ON request BEGIN
+        INSERT INTO search (entity, entity_id, body) VALUES ('request', new.id, new.description);
+    END;
+    CREATE TRIGGER request_search_update AFTER UPDATE OF description ON request BEGIN
+        UPDATE search SET body = new.description WHERE entity = 'request' AND entity_id = old.id;
+    END;
+    CREATE TRIGGER request_search_delete AFTER DELETE ON request BEGIN
+        DELETE FROM search WHERE entity = 'request' AND entity_id = old.id;
+    END;
+
+    CREATE TRIGGER work_search_insert AFTER INSERT ON work BEGIN
+        INSERT INTO search (entity, entity_id, body) VALUES ('work', new.id, new.description);
+    END;
+    CREATE TRIGGER work_search_update AFTER UPDATE OF description ON work BEGIN
+        UPDATE search SET body = new.description WHERE entity = 'work' AND entity_id = old.id;
+    END;
+    CREATE TRIGGER work_search_delete AFTER DELETE ON work BEGIN
+        DELETE FROM search WHERE entity = 'work' AND entity_id = old.id;
+    END;
+    ",
+    "
+    CREATE TABLE request_note (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        request_id INTEGER NOT NULL,
+        author TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        body TEXT NOT NULL,
+        FOREIGN KEY (request_id) REFERENCES request(id)
+    );
+    CREATE INDEX request_note_request_id ON request_note (request_id);
+    ",
+    "
+    CREATE TABLE attachment_blob (
+        sha256 TEXT PRIMARY KEY,
+        data BLOB NOT NULL
+    );
+    CREATE TABLE attachment (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        request_id INTEGER NOT NULL,
+        work_id INTEGER,
+        filename TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        sha256 TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        FOREIGN KEY (request_id) REFERENCES request(id),
+        FOREIGN KEY (work_id) REFERENCES work(id),
+        FOREIGN KEY (sha256) REFERENCES attachment_blob(sha256)
+    );
+    CREATE INDEX attachment_request_id ON attachment (request_id);
+    ",
+    "
+    CREATE TABLE audit (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        entity TEXT NOT NULL,
+        entity_id INTEGER NOT NULL,
+        action TEXT NOT NULL,
+        old_values TEXT,
+        new_values TEXT,
+        user TEXT NOT NULL,
+        changed_at TEXT NOT NULL
+    );
+    CREATE INDEX audit_entity ON audit (entity, entity_id);
+    CREATE TRIGGER audit_no_update BEFORE UPDATE ON audit BEGIN
+        SELECT RAISE(ABORT, 'audit log is append-only');
+    END;
+    CREATE TRIGGER audit_no_delete BEFORE DELETE ON audit BEGIN
+        SELECT RAISE(ABORT, 'audit log is append-only');
+    END;
+    ",
+    "
+    ALTER TABLE audit ADD COLUMN reverts INTEGER REFERENCES audit(id);
+    ",
+    "
+    ALTER TABLE customer ADD COLUMN deleted_at TEXT;
+    ALTER TABLE contract ADD COLUMN deleted_at TEXT;
+    ALTER TABLE request ADD COLUMN deleted_at TEXT;
+    ALTER TABLE work ADD COLUMN deleted_at TEXT;
+    ",
+    "
+    ALTER TABLE audit ADD COLUMN operation INTEGER;
+    ",
+];
+
+/// Splits a record saved in the audit log into column names and SQL values.
+fn json_columns(
+    record: &serde_json::Value,
+) -> Result<(Vec<String>, Vec<rusqlite::types::Value>), Error> {
+    use rusqlite::types::Value;
+
+    let object = record
+        .as_object()
+        .ok_or_else(|| Error::Undo(format!("not a record: {}", record)))?;
+    let mut columns = Vec::new();
+    let mut values = Vec::new();
+    for (column, value) in object {
+        if column == "id" {
+            continue;
+        }
+        columns.push(column.clone());
+        values.push(match value {
+            serde_json::Value::Null => Value::Null,
+            serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => Value::Integer(i),
+                None => Value::Real(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            other => Value::Text(other.to_string()),
+        });
+    }
+
+    Ok((columns, values))
+}
+
+/// How to describe an update from `old` to `new` in the audit log: moving a
+/// record into or out of the trash is recorded as such.
+fn update_action(old: &serde_json::Value, new: &serde_json::Value) -> &'static str {
+    let deleted_at = |record: &serde_json::Value| {
+        record
+            .get("deleted_at")
+            .filter(|deleted_at| !deleted_at.is_null())
+            .is_some()
+    };
+    match (deleted_at(old), deleted_at(new)) {
+        (false, true) => "trash",
+        (true, false) => "restore",
+        _ => "update",
+    }
+}
+
+/// How long to wait for another process to finish writing before failing with
+/// `database is locked`.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many backups to keep when backing up before migrating.
+const BACKUPS_KEPT: usize = 10;
+
+/// How many more times to try starting a transaction once `BUSY_TIMEOUT` is up.
+const BEGIN_RETRIES: u32 = 3;
+
+pub struct DataStore {
+    conn: Connection,
+    dir: Option<dir::Dir>,
+    /// The operation the audit rows of the open transaction belong to, once
+    /// one has been written.
+    operation: Cell<Option<u32>>,
+}
+
+impl DataStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::open_with_journal(path, Journal::Rollback)
+    }
+
+    pub fn open_with_journal<P: AsRef<Path>>(path: P, journal: Journal) -> Result<Self, Error> {
+        let conn = Connection::open(path)?;
+        Self::init(&conn)?;
+        Self::set_journal(&conn, journal)?;
+
+        Ok(DataStore::new(conn))
+    }
+
+    fn new(conn: Connection) -> Self {
+        DataStore {
+            conn,
+            dir: None,
+            operation: Cell::new(None),
+        }
+    }
+
+    pub fn add_customer(&self, name: &str) -> Result<usize, Error> {
+        let customer = Customer {
+            id: 0,
+            name: name.to_string(),
+        };
+        self.atomic(|| {
+            self.check(&customer)?;
+            self.insert_customer(&customer)
+        })?;
+        Ok(1)
+    }
+
+    pub fn add_contract(
+        &self,
+        customer_id: u32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        total_points: u32,
+        overlap: Overlap,
+    ) -> Result<usize, Error> {
+        let contract = Contract {
+            id: 0,
+            customer_id,
+            start_date: *start_date,
+            end_date: *end_date,
+            total_points,
+        };
+        self.atomic(|| {
+            self.check(&contract)?;
+            if overlap == Overlap::Reject {
+                self.check_overlap(&contract)?;
+            }
+            self.insert_contract(&contract)
+        })?;
+        Ok(1)
+    }
+
+    pub fn add_request(
+        &self,
+        contract_id: u32,
+        description: &str,
+        request_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        let request = Request {
+            id: 0,
+            contract_id,
+            description: description.to_string(),
+            request_date: *request_date,
+        };
+        self.atomic(|| {
+            self.check(&request)?;
+            self.insert_request(&request)
+        })?;
+        Ok(1)
+    }
+
+    pub fn add_work(
+        &self,
+        request_id: u32,
+        worker: &str,
+        description: &str,
+        points_used: u32,
+        work_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        let work = Work {
+            id: 0,
+            request_id,
+            worker: worker.to_string(),
+            description: description.to_string(),
+            points_used,
+            work_date: *work_date,
+        };
+        self.atomic(|| {
+            self.check_work(&work)?;
+            self.insert_work(&work)
+        })?;
+        Ok(1)
+    }
+
+    // The insert_* functions save a record as it is, without checking it, and
+    // belong inside `atomic` with the checks. They keep the record's id unless
+    // it is 0, and return the id it was saved under.
+
+    fn insert_customer(&self, customer: &Customer) -> Result<u32, Error> {
+        self.conn.execute(
+            "INSERT INTO customer (id, name) VALUES (:id, :name)",
+            named_params! {
+                ":id": (customer.id != 0).then_some(customer.id),
+                ":name": customer.name,
+            },
+        )?;
+        let id = self.last_insert_id();
+        self.audit(Entity::Customer, id, "insert", None)?;
+
+        Ok(id)
+    }
+
+    fn insert_contract(&self, contract: &Contract) -> Result<u32, Error> {
+        self.conn.execute(
+            "INSERT INTO contract (id, customer_id, start_date, end_date, total_points)
+                VALUES (:id, :customer_id, :start_date, :end_date, :total_points)",
+            named_params! {
+                ":id": (contract.id != 0).then_some(contract.id),
+                ":customer_id": contract.customer_id,
+                ":start_date": contract.start_date.to_string(),
+                ":end_date": contract.end_date.to_string(),
+                ":total_points": contract.total_points,
+            },
+        )?;
+        let id = self.last_insert_id();
+        self.audit(Entity::Contract, id, "insert", None)?;
+
+        Ok(id)
+    }
+
+    fn insert_request(&self, request: &Request) -> Result<u32, Error> {
+        self.conn.execute(
+            "INSERT INTO request (id, contract_id, description, request_date)
+                VALUES (:id, :contract_id, :description, :request_date)",
+            named_params! {
+                ":id": (request.id != 0).then_some(request.id),
+                ":contract_id": request.contract_id,
+                ":description": request.description,
+                ":request_date": request.request_date.to_string(),
+            },
+        )?;
+        let id = self.last_insert_id();
+        self.audit(Entity::Request, id, "insert", None)?;
+
+        Ok(id)
+    }
+
+    fn insert_work(&self, work: &Work) -> Result<u32, Error> {
+        self.conn.execute(
+            "INSERT INTO work (id, request_id, worker, description, points_used, work_date)
+                VALUES (:id, :request_id, :worker, :description, :points_used, :work_date)",
+            named_params! {
+                ":id": (work.id != 0).then_some(work.id),
+                ":request_id": work.request_id,
+                ":worker": work.worker,
+                ":description": work.description,
+                ":points_used": work.points_used,
+                ":work_date": work.work_date.to_string(),
+            },
+        )?;
+        let id = self.last_insert_id();
+        self.audit(Entity::Work, id, "insert", None)?;
+
+        Ok(id)
+    }
+
+    pub fn add_note(&self, request_id: u32, author: &str, body: &str) -> Result<usize, Error> {
+        self.atomic(|| {
+            let rows = self.conn.execute(
+                "INSERT INTO request_note (request_id, author, created_at, body)
+                    VALUES (:request_id, :author, :created_at, :body)",
+                named_params! {
+                    ":request_id": request_id,
+                    ":author": author,
+                    ":created_at": Utc::now().to_rfc3339(),
+                    ":body": body,
+                },
+            )?;
+            self.audit(Entity::Note, self.last_insert_id(), "insert", None)?;
+
+            Ok(rows)
+        })
+    }
+
+    /// Stores `data` as an attachment of a request, or of one of its work logs
+    /// when `work_id` is given. Identical contents are stored only once.
+    pub fn add_attachment(
+        &self,
+        request_id: u32,
+        work_id: Option<u32>,
+        filename: &str,
+        data: &[u8],
+    ) -> Result<usize, Error> {
+        let sha256 = format!("{:x}", Sha256::digest(data));
+
+        self.atomic(|| {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO attachment_blob (sha256, data) VALUES (:sha256, :data)",
+                named_params! {":sha256": sha256, ":data": data},
+            )?;
+            let rows = self.conn.execute(
+                "INSERT INTO attachment (request_id, work_id, filename, size, sha256, created_at)
+                    VALUES (:request_id, :work_id, :filename, :size, :sha256, :created_at)",
+                named_params! {
+                    ":request_id": request_id,
+                    ":work_id": work_id,
+                    ":filename": filename,
+                    ":size": data.len(),
+                    ":sha256": sha256,
+                    ":created_at": Utc::now().to_rfc3339(),
+                },
+            )?;
+            self.audit(Entity::Attachment, self.last_insert_id(), "insert", None)?;
+
+            Ok(rows)
+        })
+    }
+
+    /// Recorded changes to one entity, oldest first.
+    pub fn history(&self, entity: Entity, id: u32) -> Result<Vec<AuditEntry>, Error> {
+        let mut stmt = self.conn.prepare(
+            "select * from audit where entity = :entity and entity_id = :id order by id",
+        )?;
+        let rows = stmt.query_map(
+            named_params! {":entity": entity.to_string(), ":id": id},
+            |r| AuditEntry::try_from(r),
+        )?;
+        let mut entries = Vec::new();
+        for r in rows {
+            entries.push(r?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Appends a row to the audit log for a change from `old` to the current
+    /// state of the record. Every write in `DataStore` goes through here, inside
+    /// the same `atomic` block as the change it describes.
+    fn audit(
+        &self,
+        entity: Entity,
+        entity_id: u32,
+        action: &str,
+        old: Option<serde_json::Value>,
+    ) -> Result<(), Error> {
+        let new = self.snapshot(entity, entity_id)?;
+        if old.is_none() && new.is_none() {
+            return Ok(());
+        }
+        self.append_audit(entity, entity_id, action, old, new, None)
+    }
+
+    fn append_audit(
+        &self,
+        entity: Entity,
+        entity_id: u32,
+        action: &str,
+        old: Option<serde_json::Value>,
+        new: Option<serde_json::Value>,
+        reverts: Option<u32>,
+    ) -> Result<(), Error> {
+        let operation = match self.operation.get() {
+            Some(operation) => operation,
+            None => {
+                let operation = self.conn.query_one(
+                    "select coalesce(max(operation), 0) + 1 from audit",
+                    [],
+                    |r| r.get(0),
+                )?;
+                self.operation.set(Some(operation));
+                operation
+            }
+        };
+        self.conn.execute(
+            "INSERT INTO audit
+                (entity, entity_id, action, old_values, new_values, user, changed_at, reverts,
+                    operation)
+                VALUES
+                (:entity, :entity_id, :action, :old_values, :new_values, :user, :changed_at,
+                    :reverts, :operation)",
+            named_params! {
+                ":entity": entity.to_string(),
+                ":entity_id": entity_id,
+                ":action": action,
+                ":old_values": old.map(|v| v.to_string()),
+                ":new_values": new.map(|v| v.to_string()),
+                ":user": current_user(),
+                ":changed_at": Utc::now().to_rfc3339(),
+                ":reverts": reverts,
+                ":operation": operation,
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Reverts the `steps` most recent operations that have not been undone
+    /// yet, newest first, and returns the audit entries that were reverted. An
+    /// operation is everything one call changed, e.g. all records a purge
+    /// removed. Either all of them are reverted or none is.
+    pub fn undo(&self, steps: u32) -> Result<Vec<AuditEntry>, Error> {
+        self.atomic(|| {
+            let mut stmt = self.conn.prepare(
+                "select * from audit where reverts is null
+                    and id not in (select reverts from audit where reverts is not null)
+                    order by id desc",
+            )?;
+            let rows = stmt.query_map([], |r| AuditEntry::try_from(r))?;
+            let mut entries = Vec::new();
+            let mut operations = Vec::new();
+            for r in rows {
+                let entry = r?;
+                // Changes logged before operations were recorded stand alone.
+                let operation = entry.operation.map_or(-(entry.id as i64), i64::from);
+                if operations.last() != Some(&operation) {
+                    if operations.len() == steps as usize {
+                        break;
+                    }
+                    operations.push(operation);
+                }
+                entries.push(entry);
+            }
+
+            for entry in &entries {
+                self.revert(entry)?;
+            }
+
+            Ok(entries)
+        })
+    }
+
+    fn revert(&self, entry: &AuditEntry) -> Result<(), Error> {
+        let entity: Entity = entry.entity.parse().map_err(Error::Undo)?;
+        let table = entity.table();
+        let current = self.snapshot(entity, entry.entity_id)?;
+        if current != entry.new_values {
+            return Err(Error::Undo(format!(
+                "{} {} has changed since change {}",
+                entity, entry.entity_id, entry.id
+            )));
+        }
+
+        let action = match (&entry.old_values, &entry.new_values) {
+            (None, Some(_)) => {
+                self.conn.execute(
+                    &format!("DELETE FROM {} WHERE id = :id", table),
+                    [entry.entity_id],
+                )?;
+                "delete"
+            }
+            (Some(old), Some(new)) => {
+                let mut old = old.clone();
+                for (column, _) in new.as_object().into_iter().flatten() {
+                    if old.get(column).is_none() {
+                        old[column] = serde_json::Value::Null;
+                    }
+                }
+                let (columns, values) = json_columns(&old)?;
+                let assignments = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| format!("\"{}\" = ?{}", column, i + 2))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let mut params = vec![rusqlite::types::Value::from(entry.entity_id)];
+                params.extend(values);
+                self.conn.execute(
+                    &format!("UPDATE {} SET {} WHERE id = ?1", table, assignments),
+                    rusqlite::params_from_iter(params),
+                )?;
+                update_action(new, &old)
+            }
+            (Some(old), None) => {
+                let (columns, values) = json_columns(old)?;
+                let names = columns
+                    .iter()
+                    .map(|column| format!(", \"{}\"", column))
+                    .collect::<String>();
+                let placeholders = (0..columns.len())
+                    .map(|i| format!(", ?{}", i + 2))
+                    .collect::<String>();
+                let mut params = vec![rusqlite::types::Value::from(entry.entity_id)];
+                params.extend(values);
+                self.conn.execute(
+                    &format!(
+                        "INSERT INTO {} (id{}) VALUES (?1{})",
+                        table, names, placeholders
+                    ),
+                    rusqlite::params_from_iter(params),
+                )?;
+                "insert"
+            }
+            (None, None) => return Ok(()),
+        };
+
+        let restored = self.snapshot(entity, entry.entity_id)?;
+        self.append_audit(
+            entity,
+            entry.entity_id,
+            action,
+            current,
+            restored,
+            Some(entry.id),
+        )
+    }
+
+    /// The audit log representation of a record, or `None` if it does not exist.
+    fn snapshot(&self, entity: Entity, id: u32) -> Result<Option<serde_json::Value>, Error> {
+        let value = match entity {
+            Entity::Customer => self.get_customer(id).map(serde_json::to_value),
+            Entity::Contract => self.get_contract(id).map(serde_json::to_value),
+            Entity::Request => self.get_request(id).map(serde_json::to_value),
+            Entity::Work => self.get_work(id).map(serde_json::to_value),
+            Entity::Note => self.get_note(id).map(serde_json::to_value),
+            Entity::Attachment => self.get_attachment(id).map(serde_json::to_value),
+        };
+        let mut value = match value {
+            Ok(value) => value.unwrap(),
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if entity.is_trashable()
+            && let Some(deleted_at) = self.deleted_at(entity, id)?
+        {
+            value["deleted_at"] = deleted_at.to_rfc3339().into();
+        }
+
+        Ok(Some(value))
+    }
+
+    fn deleted_at(&self, entity: Entity, id: u32) -> Result<Option<DateTime<Utc>>, Error> {
+        let deleted_at: Option<String> = match self.conn.query_one(
+            &format!("select deleted_at from {} where id = :id", entity.table()),
+            [id],
+            |r| r.get(0),
+        ) {
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            result => result?,
+        };
+
+        Ok(deleted_at.map(|d| DateTime::parse_from_rfc3339(&d).unwrap().to_utc()))
+    }
+
+    /// Moves a record to the trash. It disappears from listings and usage but
+    /// can be brought back with `restore` until it is purged. Records that
+    /// still have live children (e.g. a contract with requests) are refused.
+    pub fn delete(&self, entity: Entity, id: u32) -> Result<usize, Error> {
+        if !entity.is_trashable() {
+            return Err(Error::NotTrashable(entity));
+        }
+        self.atomic(|| {
+            let old = self.snapshot(entity, id)?;
+            if old.is_none() {
+                return Err(Error::NotFound(entity, id));
+            }
+            if let Some((child, child_id)) = self.live_child(entity, id)? {
+                return Err(Error::InUse {
+                    entity,
+                    id,
+                    child,
+                    child_id,
+                });
+            }
+            let rows = self.conn.execute(
+                &format!(
+                    "UPDATE {} SET deleted_at = :now WHERE id = :id AND deleted_at IS NULL",
+                    entity.table()
+                ),
+                named_params! {":now": Utc::now().to_rfc3339(), ":id": id},
+            )?;
+            if rows > 0 {
+                self.audit(entity, id, "trash", old)?;
+            }
+
+            Ok(rows)
+        })
+    }
+
+    /// Takes a record back out of the trash. Its parent must not be in the
+    /// trash itself.
+    pub fn restore(&self, entity: Entity, id: u32) -> Result<usize, Error> {
+        if !entity.is_trashable() {
+            return Err(Error::NotTrashable(entity));
+        }
+        self.atomic(|| {
+            let old = self.snapshot(entity, id)?;
+            if old.is_none() {
+                return Err(Error::NotFound(entity, id));
+            }
+            if let Some((parent, parent_id)) = self.parent(entity, id)?
+                && self.deleted_at(parent, parent_id)?.is_some()
+            {
+                return Err(Error::ParentDeleted {
+                    entity,
+                    id,
+                    parent,
+                    parent_id,
+                });
+            }
+            let rows = self.conn.execute(
+                &format!(
+                    "UPDATE {} SET deleted_at = NULL WHERE id = :id AND deleted_at IS NOT NULL",
+                    entity.table()
+                ),
+                [id],
+            )?;
+            if rows > 0 {
+                self.audit(entity, id, "restore", old)?;
+            }
+
+            Ok(rows)
+        })
+    }
+
+    /// Everything in the trash, most recently deleted first.
+    pub fn list_trash(&self) -> Result<Vec<TrashEntry>, rusqlite::Error> {
+        let query = "
+            SELECT 'customer', id, name, deleted_at FROM customer
+                WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'contract', id, start_date || ' - ' || end_date, deleted_at FROM contract
+                WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'request', id, description, deleted_at FROM request
+                WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'work', id, description, deleted_at FROM work
+                WHERE deleted_at IS NOT NULL
+            ORDER BY 4 DESC";
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map([], |row| TrashEntry::try_from(row))?;
+        let mut entries = Vec::new();
+        for entry_result in rows {
+            entries.push(entry_result?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Permanently removes records that were moved to the trash before
+    /// `deleted_before`, along with the notes and attachments of purged
+    /// requests and work. Records whose children are still around are kept.
+    /// Returns what was removed.
+    pub fn purge(&self, deleted_before: DateTime<Utc>) -> Result<Vec<(Entity, u32)>, Error> {
+        self.atomic(|| {
+            let mut purged = Vec::new();
+            for entity in [
+                Entity::Work,
+                Entity::Request,
+                Entity::Contract,
+                Entity::Customer,
+            ] {
+                let mut stmt = self.conn.prepare(&format!(
+                    "select id from {} where deleted_at < :before order by id",
+                    entity.table()
+                ))?;
+                let ids = stmt
+                    .query_map([deleted_before.to_rfc3339()], |r| r.get::<_, u32>(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                for id in ids {
+                    if self.child(entity, id)?.is_some() {
+                        continue;
+                    }
+                    self.purge_dependents(entity, id)?;
+                    self.remove(entity, id)?;
+                    purged.push((entity, id));
+                }
+            }
+            // Contents the change history still refers to stay, so that undoing
+            // the removal of an attachment brings its data back too.
+            self.conn.execute(
+                "DELETE FROM attachment_blob
+                    WHERE sha256 NOT IN (SELECT sha256 FROM attachment)
+                    AND sha256 NOT IN (
+                        SELECT old_values ->> 'sha256' FROM audit
+                        WHERE entity = 'attachment' AND old_values IS NOT NULL
+                    )",
+                [],
+            )?;
+
+            Ok(purged)
+        })
+    }
+
+    fn purge_dependents(&self, entity: Entity, id: u32) -> Result<(), Error> {
+        let column = match entity {
+            Entity::Request => "request_id",
+            Entity::Work => "work_id",
+            _ => return Ok(()),
+        };
+        let mut dependents = Vec::new();
+        if entity == Entity::Request {
+            let mut stmt = self
+                .conn
+                .prepare("select id from request_note where request_id = :id")?;
+            for note_id in stmt.query_map([id], |r| r.get::<_, u32>(0))? {
+                dependents.push((Entity::Note, note_id?));
+            }
+        }
+        let mut stmt = self
+            .conn
+            .prepare(&format!("select id from attachment where {} = :id", column))?;
+        for attachment_id in stmt.query_map([id], |r| r.get::<_, u32>(0))? {
+            dependents.push((Entity::Attachment, attachment_id?));
+        }
+        for (entity, id) in dependents {
+            self.remove(entity, id)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes a row for good, recording its last state in the audit log.
+    fn remove(&self, entity: Entity, id: u32) -> Result<(), Error> {
+        let old = self.snapshot(entity, id)?;
+        self.conn.execute(
+            &format!("DELETE FROM {} WHERE id = :id", entity.table()),
+            [id],
+        )?;
+        self.audit(entity, id, "delete", old)
+    }
+
+    fn parent(&self, entity: Entity, id: u32) -> Result<Option<(Entity, u32)>, Error> {
+        let (parent, column) = match entity {
+            Entity::Contract => (Entity::Customer, "customer_id"),
+            Entity::Request => (Entity::Contract, "contract_id"),
+            Entity::Work => (Entity::Request, "request_id"),
+            _ => return Ok(None),
+        };
+        let parent_id = self.conn.query_one(
+            &format!("select {} from {} where id = :id", column, entity.table()),
+            [id],
+            |r| r.get(0),
+        )?;
+
+        Ok(Some((parent, parent_id)))
+    }
+
+    /// Some child of a record that is not in the trash, if there is one.
+    fn live_child(&self, entity: Entity, id: u32) -> Result<Option<(Entity, u32)>, Error> {
+        self.find_child(entity, id, "AND deleted_at IS NULL")
+    }
+
+    /// Some child of a record, whether in the trash or not, if there is one.
+    fn child(&self, entity: Entity, id: u32) -> Result<Option<(Entity, u32)>, Error> {
+        self.find_child(entity, id, "")
+    }
+
+    fn find_child(
+        &self,
+        entity: Entity,
+        id: u32,
+        condition: &str,
+    ) -> Result<Option<(Entity, u32)>, Error> {
+        let (child, column) = match entity {
+            Entity::Customer => (Entity::Contract, "customer_id"),
+            Entity::Contract => (Entity::Request, "contract_id"),
+            Entity::Request => (Entity::Work, "request_id"),
+            _ => return Ok(None),
+        };
+        let child_id = self.conn.query_one(
+            &format!(
+                "select min(id) from {} where {} = :id {}",
+                child.table(),
+                column,
+                condition
+            ),
+            [id],
+            |r| r.get::<_, Option<u32>>(0),
+        )?;
+
+        Ok(child_id.map(|child_id| (child, child_id)))
+    }
+
+    fn check<T: Validate>(&self, entity: &T) -> Result<(), Error> {
+        let errors = entity.validate();
+        if !errors.is_empty() {
+            return Err(Error::Validation(errors));
+        }
+        Ok(())
+    }
+
+    /// Fails if `contract` overlaps in time with another contract of the same
+    /// customer, since it would then be unclear which one a request draws from.
+    fn check_overlap(&self, contract: &Contract) -> Result<(), Error> {
+        let mut stmt = self.conn.prepare(
+            "select id from contract
+                where customer_id = :customer_id
+                and id != :id
+                and deleted_at is null
+                and start_date <= :end_date
+                and end_date >= :start_date
+                order by id",
+        )?;
+        let ids = stmt
+            .query_map(
+                named_params! {
+                    ":customer_id": contract.customer_id,
+                    ":id": contract.id,
+                    ":start_date": contract.start_date.to_string(),
+                    ":end_date": contract.end_date.to_string(),
+                },
+                |r| r.get::<_, u32>(0),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        if !ids.is_empty() {
+            return Err(Error::Overlap(ids));
+        }
+        Ok(())
+    }
+
+    /// Like `check`, but also makes sure the work is not dated before its request.
+    fn check_work(&self, work: &Work) -> Result<(), Error> {
+        let mut errors = work.validate();
+        match self.get_request(work.request_id) {
+            Ok(request) if work.work_date < request.request_date => {
+                errors.push(FieldError::new(
+                    "work_date",
+                    &format!(
+                        "must not be before the request date ({})",
+                        request.request_date
+                    ),
+                ));
+            }
+            Ok(_) | Err(rusqlite::Error::QueryReturnedNoRows) => {}
+            Err(e) => return Err(e.into()),
+        }
+        if !errors.is_empty() {
+            return Err(Error::Validation(errors));
+        }
+        Ok(())
+    }
+
+    /// Saves the records of an `export`, all or nothing. Restoring keeps their
+    /// ids and needs a database without customers, contracts, requests or work,
+    /// trashed ones included; merging gives them new ids and updates the
+    /// references between them to match. `overlap` applies to merged contracts
+    /// like to `add_contract`.
+    pub fn import(
+        &self,
+        export: &Export,
+        merge: bool,
+        overlap: Overlap,
+    ) -> Result<ImportSummary, Error> {
+        if export.version > EXPORT_VERSION {
+            return Err(Error::Unsupported(format!(
+                "export version {} (this build reads up to {})",
+                export.version, EXPORT_VERSION
+            )));
+        }
+        if !merge {
+            for table in ["customer", "contract", "request", "work"] {
+                let count: u32 =
+                    self.conn
+                        .query_one(&format!("select count(*) from {}", table), [], |r| r.get(0))?;
+                if count > 0 {
+                    return Err(Error::NotEmpty);
+                }
+            }
+        }
+
+        // Ids in the export mapped to the ids the records were saved under.
+        let mut customers = HashMap::new();
+        let mut contracts = HashMap::new();
+        let mut requests = HashMap::new();
+        let mut work = 0;
+        let mut errors = Vec::new();
+        let keep = |id: u32| if merge { 0 } else { id };
+        // A restore takes back whatever was exported, even records that fail
+        // today's checks; a merge checks the records like new ones.
+        let check = |check: &dyn Fn() -> Result<(), Error>| if merge { check() } else { Ok(()) };
+        let parent = |ids: &HashMap<u32, u32>, entity: Entity, id: u32| {
+            ids.get(&id).copied().ok_or_else(|| {
+                Error::Validation(vec![FieldError::new(
+                    &format!("{}_id", entity),
+                    &format!("{} {} is not in the export or failed to import", entity, id),
+                )])
+            })
+        };
+        let failure =
+            |section: &'static str, index: usize, entity: Entity, id: u32, e: Error| RowError {
+                section: Some(section),
+                row: index as u64 + 1,
+                message: format!("{} {}: {}", entity, id, e).replace("\n  ", " "),
+            };
+
+        self.atomic(|| {
+            for (i, c) in export.customers.iter().enumerate() {
+                let customer = Customer {
+                    id: keep(c.id),
+                    name: c.name.clone(),
+                };
+                let result =
+                    check(&|| self.check(&customer)).and_then(|()| self.insert_customer(&customer));
+                match result {
+                    Ok(id) => {
+                        customers.insert(c.id, id);
+                    }
+                    Err(e) => errors.push(failure("customers", i, Entity::Customer, c.id, e)),
+                }
+            }
+            for (i, c) in export.contracts.iter().enumerate() {
+                let result =
+                    parent(&customers, Entity::Customer, c.customer_id).and_then(|customer_id| {
+                        let contract = Contract {
+                            id: keep(c.id),
+                            customer_id,
+                            start_date: c.start_date,
+                            end_date: c.end_date,
+                            total_points: c.total_points,
+                        };
+                        check(&|| {
+                            self.check(&contract)?;
+                            if overlap == Overlap::Reject {
+                                self.check_overlap(&contract)?;
+                            }
+                            Ok(())
+                        })?;
+                        self.insert_contract(&contract)
+                    });
+                match result {
+                    Ok(id) => {
+                        contracts.insert(c.id, id);
+                    }
+                    Err(e) => errors.push(failure("contracts", i, Entity::Contract, c.id, e)),
+                }
+            }
+            for (i, r) in export.requests.iter().enumerate() {
+                let result =
+                    parent(&contracts, Entity::Contract, r.contract_id).and_then(|contract_id| {
+                        let request = Request {
+                            id: keep(r.id),
+                            contract_id,
+                            description: r.description.clone(),
+                            request_date: r.request_date,
+                        };
+                        check(&|| self.check(&request))?;
+                        self.insert_request(&request)
+                    });
+                match result {
+                    Ok(id) => {
+                        requests.insert(r.id, id);
+                    }
+                    Err(e) => errors.push(failure("requests", i, Entity::Request, r.id, e)),
+                }
+            }
+            for (i, w) in export.work.iter().enumerate() {
+                let result =
+                    parent(&requests, Entity::Request, w.request_id).and_then(|request_id| {
+                        let work = Work {
+                            id: keep(w.id),
+                            request_id,
+                            worker: w.worker.clone(),
+                            description: w.description.clone(),
+                            points_used: w.points_used,
+                            work_date: w.work_date,
+                        };
+                        check(&|| self.check_work(&work))?;
+                        self.insert_work(&work)
+                    });
+                match result {
+                    Ok(_) => work += 1,
+                    Err(e) => errors.push(failure("work", i, Entity::Work, w.id, e)),
+                }
+            }
+
+            if !errors.is_empty() {
+                return Err(Error::Rows(errors));
+            }
+            Ok(ImportSummary {
+                customers: customers.len(),
+                contracts: contracts.len(),
+                requests: requests.len(),
+                work,
+            })
+        })
+    }
+
+    /// Runs `f` inside a transaction, rolling back everything it did if it
+    /// fails. Nested blocks use savepoints, so atomic blocks can call each
+    /// other. The changes of the outermost block form one operation, which
+    /// `undo` reverts as a whole.
+    fn atomic<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let outermost = self.conn.is_autocommit();
+        let (commit, rollback) = if outermost {
+            Self::begin(&self.conn)?;
+            ("COMMIT", "ROLLBACK")
+        } else {
+            self.conn.execute_batch("SAVEPOINT atomic")?;
+            ("RELEASE atomic", "ROLLBACK TO atomic; RELEASE atomic")
+        };
+        let result = f();
+        if outermost {
+            self.operation.set(None);
+        }
+        match result {
+            Ok(value) => {
+                self.conn.execute_batch(commit)?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.conn.execute_batch(rollback)?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Starts a write transaction. Taking the write lock up front means other
+    /// writers wait for it in the busy handler, instead of failing halfway
+    /// through with `database is locked`. If the lock is still held after
+    /// `BUSY_TIMEOUT`, tries again a few times before giving up.
+    fn begin(conn: &Connection) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            match conn.execute_batch("BEGIN IMMEDIATE") {
+                Err(rusqlite::Error::SqliteFailure(e, _))
+                    if matches!(
+                        e.code,
+                        rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                    ) && attempt < BEGIN_RETRIES =>
+                {
+                    attempt += 1;
+                    std::thread::sleep(Duration::from_millis(50 << attempt));
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    fn last_insert_id(&self) -> u32 {
+        self.conn.last_insert_rowid() as u32
+    }
+
+    /// Copies the database into `dir`, by default `<database>.backups` next to
+    /// it, as `maint-<timestamp>.db`. Uses SQLite's online backup, so the copy
+    /// is consistent even while others write. Only the newest `keep` backups in
+    /// `dir` are kept. Returns the path of the new backup.
+    pub fn backup(&self, dir: Option<&Path>, keep: usize) -> Result<PathBuf, Error> {
+        let Some(path) = self.file_path() else {
+            return Err(Error::Unsupported(
+                "backups of in-memory or directory stores".to_string(),
+            ));
+        };
+        let dir = dir.map_or_else(|| Self::backup_dir(&path), Path::to_path_buf);
+        Self::backup_into(&self.conn, &dir, keep)
+    }
+
+    /// Replaces the database with the backup at `file`, after checking that it
+    /// is intact and was made by maint. The database as it was is backed up
+    /// first; returns where to.
+    pub fn restore_backup(&self, file: &Path) -> Result<PathBuf, Error> {
+        let Some(path) = self.file_path() else {
+            return Err(Error::Unsupported(
+                "restoring in-memory or directory stores".to_string(),
+            ));
+        };
+        Self::check_backup(file)?;
+        // Pruning now could remove the backup being restored.
+        let saved = Self::backup_into(&self.conn, &Self::backup_dir(&path), usize::MAX)?;
+        // Restoring needs a connection of its own.
+        let mut conn = Connection::open(&path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.restore(
+            rusqlite::MAIN_DB,
+            file,
+            None::<fn(rusqlite::backup::Progress)>,
+        )?;
+        // Older backups still need the newer migrations.
+        Self::init(&self.conn)?;
+
+        Ok(saved)
+    }
+
+    /// The database file, unless this is an in-memory or directory store.
+    fn file_path(&self) -> Option<PathBuf> {
+        match self.dir {
+            Some(_) => None,
+            None => Self::database_path(&self.conn),
+        }
+    }
+
+    fn database_path(conn: &Connection) -> Option<PathBuf> {
+        conn.path().filter(|p| !p.is_empty()).map(PathBuf::from)
+    }
+
+    fn backup_dir(database: &Path) -> PathBuf {
+        let mut dir = database.as_os_str().to_owned();
+        dir.push(".backups");
+        PathBuf::from(dir)
+    }
+
+    fn backup_into(conn: &Connection, dir: &Path, keep: usize) -> Result<PathBuf, Error> {
+        const TIMESTAMP: &str = "%Y%m%d-%H%M%S-%3f";
+        std::fs::create_dir_all(dir)?;
+        let path = loop {
+            let name = format!("maint-{}.db", Utc::now().format(TIMESTAMP));
+            let path = dir.join(name);
+            if !path.exists() {
+                break path;
+            }
+        };
+        conn.backup(rusqlite::MAIN_DB, &path, None)?;
+
+        // The timestamps make the names sort oldest first. Only files named
+        // like backups are pruned, as the folder may hold other databases.
+        let mut backups = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let is_backup = name
+                .strip_prefix("maint-")
+                .and_then(|name| name.strip_suffix(".db"))
+                .is_some_and(|stamp| NaiveDateTime::parse_from_str(stamp, TIMESTAMP).is_ok());
+            if is_backup {
+                backups.push(name);
+            }
+        }
+        backups.sort();
+        for name in &backups[..backups.len().saturating_sub(keep.max(1))] {
+            std::fs::remove_file(dir.join(name))?;
+        }
+
+        Ok(path)
+    }
+
+    /// Fails unless `file` is an intact SQLite database made by this or an
+    /// older version of maint.
+    fn check_backup(file: &Path) -> Result<(), Error> {
+        let invalid = |message: String| Error::Format {
+            path: file.to_path_buf(),
+            message,
+        };
+        if !file.is_file() {
+            return Err(invalid("no such file".to_string()));
+        }
+        let conn = Connection::open_with_flags(file, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let problems = conn
+            .prepare("PRAGMA integrity_check")
+            .and_then(|mut stmt| {
+                stmt.query_map([], |r| r.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .map_err(|e| invalid(e.to_string()))?;
+        if problems != ["ok"] {
+            return Err(invalid(format!(
+                "failed the integrity check: {}",
+                problems.join("; ")
+            )));
+        }
+        let version: usize = conn.pragma_query_value(None, "user_version", |r| r.get(0))?;
+        // Backups of databases from before the migrations were numbered have
+        // no version, but do have the customer table.
+        let customers: usize = conn.query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'customer'",
+            [],
+            |r| r.get(0),
+        )?;
+        if version == 0 && customers == 0 {
+            return Err(invalid("not a maint database".to_string()));
+        }
+        if version > MIGRATIONS.len() {
+            return Err(invalid("made by a newer version of maint".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Switches a database file to `journal`, as the file remembers it for
+    /// everyone. Switching needs the file to itself; while others use it, it
+    /// stays as it is. In-memory databases keep their own journal.
+    fn set_journal(conn: &Connection, journal: Journal) -> Result<(), Error> {
+        if Self::database_path(conn).is_none() {
+            return Ok(());
+        }
+        let mode = match journal {
+            Journal::Rollback => "delete",
+            Journal::Wal => "wal",
+        };
+        let current: String = conn.pragma_query_value(None, "journal_mode", |r| r.get(0))?;
+        if current.eq_ignore_ascii_case(mode) {
+            return Ok(());
+        }
+        match conn.pragma_update_and_check(None, "journal_mode", mode, |r| r.get::<_, String>(0)) {
+            Ok(_) => Ok(()),
+            Err(rusqlite::Error::SqliteFailure(e, _))
+                if matches!(
+                    e.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                ) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn init(conn: &Connection) -> Result<(), Error> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+
+        let version = |conn: &Connection| -> Result<usize, Error> {
+            Ok(conn.pragma_query_value(None, "user_version", |r| r.get(0))?)
+        };
+        let current = version(conn)?;
+        if current == MIGRATIONS.len() {
+            return Ok(());
+        }
+        // Databases from before the migrations were numbered have tables but
+        // no version, so only a file without any tables is new.
+        let tables: usize =
+            conn.query_row("SELECT count(*) FROM sqlite_master", [], |r| r.get(0))?;
+        if tables > 0
+            && let Some(path) = Self::database_path(conn)
+        {
+            Self::backup_into(conn, &Self::backup_dir(&path), BACKUPS_KEPT)?;
+        }
+        Self::begin(conn)?;
+        // Someone else may have migrated while we waited for the lock.
+        let result = version(conn).and_then(|current| {
+            for (i, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+                conn.execute_batch(migration)?;
+                conn.pragma_update(None, "user_version", i + 1)?;
+            }
+            Ok(())
+        });
+        conn.execute_batch(if result.is_ok() { "COMMIT" } else { "ROLLBACK" })?;
+
+        result
+    }
+
+    pub fn list_customer(&self) -> Result<Vec<Customer>, rusqlite::Error> {
+        let query = "select * from customer where deleted_at is null";
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map([], |row| Customer::try_from(row))?;
+        let mut customers = Vec::new();
+        for customer_result in rows {
+            customers.push(customer_result?);
+        }
+
+        Ok(customers)
+    }
+
+    pub fn list_contract(&self) -> Result<Vec<Contract>, rusqlite::Error> {
+        let query = "select * from contract where deleted_at is null";
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map([], |row| Contract::try_from(row))?;
+        let mut contracts = Vec::new();
+        for contract_result in rows {
+            contracts.push(contract_result?);
+        }
+
+        Ok(contracts)
+    }
+
+    pub fn list_request(&self) -> Result<Vec<Request>, rusqlite::Error> {
+        let query = "select * from request where deleted_at is null";
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map([], |row| Request::try_from(row))?;
+        let mut requests = Vec::new();
+        for request_result in rows {
+            requests.push(request_result?);
+        }
+
+        Ok(requests)
+    }
+
+    pub fn list_work(&self) -> Result<Vec<Work>, rusqlite::Error> {
+        let query = "select * from work where deleted_at is null";
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map([], |row| Work::try_from(row))?;
+        let mut work_entries = Vec::new();
+        for work_result in rows {
+            work_entries.push(work_result?);
+        }
+
+        Ok(work_entries)
+    }
+
+    /// Notes on a request, oldest first.
+    pub fn list_notes(&self, request_id: u32) -> Result<Vec<RequestNote>, rusqlite::Error> {
+        let query = "select * from request_note where request_id = :request_id
+            order by created_at, id";
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map([request_id], |row| RequestNote::try_from(row))?;
+        let mut notes = Vec::new();
+        for note_result in rows {
+            notes.push(note_result?);
+        }
+
+        Ok(notes)
+    }
+
+    /// Attachments of a request and of its work logs.
+    pub fn list_attachments(&self, request_id: u32) -> Result<Vec<Attachment>, rusqlite::Error> {
+        let query = "select * from attachment where request_id = :request_id order by id";
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt.query_map([request_id], |row| Attachment::try_from(row))?;
+        let mut attachments = Vec::new();
+        for attachment_result in rows {
+            attachments.push(attachment_result?);
+        }
+
+        Ok(attachments)
+    }
+
+    pub fn get_customer(&self, id: u32) -> Result<Customer, rusqlite::Error> {
+        self.conn
+            .query_one("select * from customer where id = :id", [id], |r| {
+                Customer::try_from(r)
+            })
+    }
+
+    pub fn get_contract(&self, id: u32) -> Result<Contract, rusqlite::Error> {
+        self.conn
+            .query_one("select * from contract where id = :id", [id], |r| {
+                Contract::try_from(r)
+            })
+    }
+
+    pub fn get_request(&self, id: u32) -> Result<Request, rusqlite::Error> {
+        self.conn
+            .query_one("select * from request where id = :id", [id], |r| {
+                Request::try_from(r)
+            })
+    }
+
+    pub fn get_work(&self, id: u32) -> Result<Work, rusqlite::Error> {
+        self.conn
+            .query_one("select * from work where id = :id", [id], |r| {
+                Work::try_from(r)
+            })
+    }
+
+    pub fn get_note(&self, id: u32) -> Result<RequestNote, rusqlite::Error> {
+        self.conn
+            .query_one("select * from request_note where id = :id", [id], |r| {
+                RequestNote::try_from(r)
+            })
+    }
+
+    pub fn get_attachment(&self, id: u32) -> Result<Attachment, rusqlite::Error> {
+        self.conn
+            .query_one("select * from attachment where id = :id", [id], |r| {
+                Attachment::try_from(r)
+            })
+    }
+
+    pub fn attachment_data(&self, id: u32) -> Result<Vec<u8>, rusqlite::Error> {
+        self.conn.query_one(
+            "select attachment_blob.data from attachment
+                inner join attachment_blob on attachment.sha256 = attachment_blob.sha256
+                where attachment.id = :id",
+            [id],
+            |r| r.get(0),
+        )
+    }
+
+    pub fn save_customer(&self, entity: Customer) -> Result<usize, Error> {
+        self.atomic(|| {
+            self.check(&entity)?;
+            let old = self.snapshot(Entity::Customer, entity.id)?;
+            let rows = self.conn.execute(
+                "UPDATE customer SET name = :name WHERE id = :id",
+                &[(":name", &entity.name), (":id", &entity.id.to_string())],
+            )?;
+            self.audit(Entity::Customer, entity.id, "update", old)?;
+
+            Ok(rows)
+        })
+    }
+
+    pub fn save_contract(&self, entity: Contract, overlap: Overlap) -> Result<usize, Error> {
+        self.atomic(|| {
+            self.check(&entity)?;
+            let old = self.get_contract(entity.id).optional()?;
+            if overlap == Overlap::Reject && old.is_some_and(|old| entity.moved(&old)) {
+                self.check_overlap(&entity)?;
+            }
+            let old = self.snapshot(Entity::Contract, entity.id)?;
+            let rows = self.conn.execute(
+                "UPDATE contract SET
+                    customer_id = :customer_id,
+                    start_date = :start_date,
+                    end_date = :end_date,
+                    total_points = :total_points
+                WHERE
+                    id = :id",
+                &[
+                    (":customer_id", &entity.customer_id.to_string()),
+                    (":start_date", &entity.start_date.to_string()),
+                    (":end_date", &entity.end_date.to_string()),
+                    (":total_points", &entity.total_points.to_string()),
+                    (":id", &entity.id.to_string()),
+                ],
+            )?;
+            self.audit(Entity::Contract, entity.id, "update", old)?;
+
+            Ok(rows)
+        })
+    }
+
+    pub fn save_request(&self, entity: Request) -> Result<usize, Error> {
+        self.atomic(|| {
+            self.check(&entity)?;
+            let old = self.snapshot(Entity::Request, entity.id)?;
+            let rows = self.conn.execute(
+                "UPDATE request SET
+                    contract_id = :contract_id,
+                    description = :description,
+                    request_date = :request_date
+                WHERE
+                    id = :id",
+                &[
+                    (":contract_id", &entity.contract_id.to_string()),
+                    (":description", &entity.description),
+                    (":request_date", &entity.request_date.to_string()),
+                    (":id", &entity.id.to_string()),
+                ],
+            )?;
+            self.audit(Entity::Request, entity.id, "update", old)?;
+
+            Ok(rows)
+        })
+    }
+
+    pub fn save_work(&self, entity: Work) -> Result<usize, Error> {
+        self.atomic(|| {
+            self.check_work(&entity)?;
+            let old = self.snapshot(Entity::Work, entity.id)?;
+            let rows = self.conn.execute(
+                "UPDATE work SET
+                    request_id = :request_id,
+                    worker = :worker,
+                    description = :description,
+                    points_used = :points_used,
+                    work_date = :work_date
+                WHERE
+                    id = :id",
+                &[
+                    (":request_id", &entity.request_id.to_string()),
+                    (":worker", &entity.worker),
+                    (":description", &entity.description),
+                    (":points_used", &entity.points_used.to_string()),
+                    (":work_date", &entity.work_date.to_string()),
+                    (":id", &entity.id.to_string()),
+                ],
+            )?;
+            self.audit(Entity::Work, entity.id, "update", old)?;
+
+            Ok(rows)
+        })
+    }
+
+    pub fn usage(&self, contract_id: u32, date: NaiveDate) -> Result<ContractUsage, Error> {
+        let contract = self.get_contract(contract_id)?;
+
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT
+                request.request_date,
+                request.description,
+                work.worker,
+                work.work_date,
+                work.description,
+                work.points_used,
+                SUM(work.points_used)
+                    OVER (
+                        PARTITION BY contract.id
+                        ORDER BY request.request_date, work.work_date
+                        ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+                    ) AS cumulative_points_used,
+                request.id
+            FROM
+                contract
+                INNER JOIN request ON contract.id = request.contract_id
+                INNER JOIN work ON request.id = work.request_id
+            WHERE
+                contract.id = :id
+                AND request.request_date BETWEEN contract.start_date and contract.end_date
+                AND work.work_date <= :date
+                AND request.deleted_at IS NULL
+                AND work.deleted_at IS NULL
+            ",
+        )?;
+
+        let rows = stmt.query_map(
+            named_params! {":id": contract_id, ":date": date.to_string() },
+            |r| CumulativeUsage::try_from(r),
+        )?;
+        let mut results = Vec::new();
+        for r in rows {
+            results.push(r?);
+        }
+
+        let contract_usage = ContractUsage {
+            contract_id: contract.id,
+            start_date: contract.start_date,
+            end_date: contract.end_date,
+            total_points: contract.total_points,
+            cumulative_usage: results,
+        };
+
+        Ok(contract_usage)
+    }
+
+    /// Full-text search over customer names and request and work descriptions,
+    /// best matches first. Every whitespace-separated term of `query` must match.
+    pub fn search(&self, query: &str, limit: u32) -> Result<Vec<SearchHit>, Error> {
+        let query = query
+            .split_whitespace()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.conn.prepare(
+            "
+            SELECT
+                search.entity,
+                search.entity_id,
+                customer.id,
+                customer.name,
+                snippet(search, 2, '[', ']', '...', 12)
+            FROM
+                search
+                LEFT JOIN work
+                    ON search.entity = 'work' AND work.id = search.entity_id
+                LEFT JOIN request
+                    ON (search.entity = 'request' AND request.id = search.entity_id)
+                    OR request.id = work.request_id
+                LEFT JOIN contract ON contract.id = request.contract_id
+                LEFT JOIN customer
+                    ON (search.entity = 'customer' AND customer.id = search.entity_id)
+                    OR customer.id = contract.customer_id
+            WHERE
+                search MATCH :query
+                AND (search.entity != 'customer' OR customer.deleted_at IS NULL)
+                AND (search.entity != 'request' OR request.deleted_at IS NULL)
+                AND (search.entity != 'work' OR work.deleted_at IS NULL)
+            ORDER BY
+                search.rank
+            LIMIT :limit
+            ",
+        )?;
+
+        let rows = stmt.query_map(named_params! {":query": query, ":limit": limit}, |r| {
+            SearchHit::try_from(r)
+        })?;
+        let mut hits = Vec::new();
+        for r in rows {
+            hits.push(r?);
+        }
+
+        Ok(hits)
+    }
+}
+
+impl Store for DataStore {
+    fn add_customer(&self, name: &str) -> Result<usize, Error> {
+        DataStore::add_customer(self, name)
+    }
+
+    fn add_contract(
+        &self,
+        customer_id: u32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        total_points: u32,
+        overlap: Overlap,
+    ) -> Result<usize, Error> {
+        DataStore::add_contract(
+            self,
+            customer_id,
+            start_date,
+            end_date,
+            total_points,
+            overlap,
+        )
+    }
+
+    fn add_request(
+        &self,
+        contract_id: u32,
+        description: &str,
+        request_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        DataStore::add_request(self, contract_id, description, request_date)
+    }
+
+    fn add_work(
+        &self,
+        request_id: u32,
+        worker: &str,
+        description: &str,
+        points_used: u32,
+        work_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        DataStore::add_work(
+            self,
+            request_id,
+            worker,
+            description,
+            points_used,
+            work_date,
+        )
+    }
+
+    fn get_customer(&self, id: u32) -> Result<Customer, Error> {
+        found(DataStore::get_customer(self, id), Entity::Customer, id)
+    }
+
+    fn get_contract(&self, id: u32) -> Result<Contract, Error> {
+        found(DataStore::get_contract(self, id), Entity::Contract, id)
+    }
+
+    fn get_request(&self, id: u32) -> Result<Request, Error> {
+        found(DataStore::get_request(self, id), Entity::Request, id)
+    }
+
+    fn get_work(&self, id: u32) -> Result<Work, Error> {
+        found(DataStore::get_work(self, id), Entity::Work, id)
+    }
+
+    fn list_customer(&self) -> Result<Vec<Customer>, Error> {
+        Ok(DataStore::list_customer(self)?)
+    }
+
+    fn list_contract(&self) -> Result<Vec<Contract>, Error> {
+        Ok(DataStore::list_contract(self)?)
+    }
+
+    fn list_request(&self) -> Result<Vec<Request>, Error> {
+        Ok(DataStore::list_request(self)?)
+    }
+
+    fn list_work(&self) -> Result<Vec<Work>, Error> {
+        Ok(DataStore::list_work(self)?)
+    }
+
+    fn save_customer(&self, entity: Customer) -> Result<usize, Error> {
+        DataStore::save_customer(self, entity)
+    }
+
+    fn save_contract(&self, entity: Contract, overlap: Overlap) -> Result<usize, Error> {
+        DataStore::save_contract(self, entity, overlap)
+    }
+
+    fn save_request(&self, entity: Request) -> Result<usize, Error> {
+        DataStore::save_request(self, entity)
+    }
+
+    fn save_work(&self, entity: Work) -> Result<usize, Error> {
+        DataStore::save_work(self, entity)
+    }
+
+    fn delete(&self, entity: Entity, id: u32) -> Result<usize, Error> {
+        DataStore::delete(self, entity, id)
+    }
+
+    fn usage(&self, contract_id: u32, date: NaiveDate) -> Result<ContractUsage, Error> {
+        DataStore::usage(self, contract_id, date)
+    }
+
+    fn batch(&self, dry_run: bool, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        self.atomic(|| {
+            self.conn.execute_batch("SAVEPOINT batch")?;
+            let result = f();
+            if result.is_err() || dry_run {
+                self.conn.execute_batch("ROLLBACK TO batch")?;
+            }
+            self.conn.execute_batch("RELEASE batch")?;
+            result
+        })
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        DataStore::flush(self)
+    }
+
+    fn add_note(&self, request_id: u32, author: &str, body: &str) -> Result<usize, Error> {
+        DataStore::add_note(self, request_id, author, body)
+    }
+
+    fn list_notes(&self, request_id: u32) -> Result<Vec<RequestNote>, Error> {
+        Ok(DataStore::list_notes(self, request_id)?)
+    }
+
+    fn add_attachment(
+        &self,
+        request_id: u32,
+        work_id: Option<u32>,
+        filename: &str,
+        data: &[u8],
+    ) -> Result<usize, Error> {
+        DataStore::add_attachment(self, request_id, work_id, filename, data)
+    }
+
+    fn list_attachments(&self, request_id: u32) -> Result<Vec<Attachment>, Error> {
+        Ok(DataStore::list_attachments(self, request_id)?)
+    }
+
+    fn get_attachment(&self, id: u32) -> Result<Attachment, Error> {
+        found(DataStore::get_attachment(self, id), Entity::Attachment, id)
+    }
+
+    fn attachment_data(&self, id: u32) -> Result<Vec<u8>, Error> {
+        found(DataStore::attachment_data(self, id), Entity::Attachment, id)
+    }
+
+    fn history(&self, entity: Entity, id: u32) -> Result<Vec<AuditEntry>, Error> {
+        DataStore::history(self, entity, id)
+    }
+
+    fn undo(&self, steps: u32) -> Result<Vec<AuditEntry>, Error> {
+        DataStore::undo(self, steps)
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashEntry>, Error> {
+        Ok(DataStore::list_trash(self)?)
+    }
+
+    fn restore(&self, entity: Entity, id: u32) -> Result<usize, Error> {
+        DataStore::restore(self, entity, id)
+    }
+
+    fn purge(&self, deleted_before: DateTime<Utc>) -> Result<Vec<(Entity, u32)>, Error> {
+        DataStore::purge(self, deleted_before)
+    }
+
+    fn search(&self, query: &str, limit: u32) -> Result<Vec<SearchHit>, Error> {
+        DataStore::search(self, query, limit)
+    }
+
+    fn import(
+        &self,
+        export: &Export,
+        merge: bool,
+        overlap: Overlap,
+    ) -> Result<ImportSummary, Error> {
+        DataStore::import(self, export, merge, overlap)
+    }
+
+    fn backup(&self, dir: Option<&Path>, keep: usize) -> Result<PathBuf, Error> {
+        DataStore::backup(self, dir, keep)
+    }
+
+    fn restore_backup(&self, file: &Path) -> Result<PathBuf, Error> {
+        DataStore::restore_backup(self, file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_memory_datastore() -> DataStore {
+        let conn = Connection::open_in_memory().unwrap();
+        DataStore::init(&conn).unwrap();
+        DataStore::new(conn)
+    }
+
+    #[test]
+    fn test_init() {
+        let ds = in_memory_datastore();
+        assert!(ds.conn.table_exists(Some("main"), "customer").unwrap());
+        assert!(ds.conn.table_exists(Some("main"), "contract").unwrap());
+        assert!(ds.conn.table_exists(Some("main"), "request").unwrap());
+        assert!(ds.conn.table_exists(Some("main"), "work").unwrap());
+        assert!(ds.conn.table_exists(Some("main"), "search").unwrap());
+        assert!(ds.conn.table_exists(Some("main"), "request_note").unwrap());
+        assert!(ds.conn.table_exists(Some("main"), "attachment").unwrap());
+        assert!(ds.conn.table_exists(Some("main"), "audit").unwrap());
+    }
+
+    #[test]
+    fn test_customer() {
+        let ds = in_memory_datastore();
+        let nrow = ds.add_customer("test customer").unwrap();
+        assert_eq!(1, nrow);
+        let customers = ds.list_customer().unwrap();
+        let customer = &customers[0];
+        assert_eq!(1, customer.id);
+        assert_eq!("test customer", customer.name);
+    }
+
+    #[test]
+    fn test_contract() {
+        let ds = in_memory_datastore();
+        let nrow = ds
+            .add_contract(
+                1,
+                &"2025-01-01".parse().unwrap(),
+                &"2025-12-31".parse().unwrap(),
+                10,
+                Overlap::Reject,
+            )
+            .unwrap();
+        assert_eq!(1, nrow);
+        let contracts = ds.list_contract().unwrap();
+        let contract = &contracts[0];
+        assert_eq!(1, contract.id);
+        assert_eq!(1, contract.customer_id);
+        assert_eq!(
+            chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            contract.start_date
+        );
+        assert_eq!(
+            chrono::NaiveDate::from_ymd_opt(2025, 12, 31).unwrap(),
+            contract.end_date
+        );
+        assert_eq!(10, contract.total_points);
+    }
+
+    #[test]
+    fn test_request() {
+        let ds = in_memory_datastore();
+        let nrow = ds
+            .add_request(1, "desc", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        assert_eq!(1, nrow);
+        let requests = ds.list_request().unwrap();
+        let request = &requests[0];
+        assert_eq!(1, request.id);
+        assert_eq!(1, request.contract_id);
+        assert_eq!("desc", request.description);
+        assert_eq!(
+            chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            request.request_date
+        );
+    }
+
+    #[test]
+    fn test_work() {
+        let ds = in_memory_datastore();
+        let nrow = ds
+            .add_work(1, "worker", "desc", 1, &"2025-01-01".parse().unwrap())
+            .unwrap();
+        assert_eq!(1, nrow);
+        let work_entries = ds.list_work().unwrap();
+        let work_entry = &work_entries[0];
+        assert_eq!(1, work_entry.id);
+        assert_eq!(1, work_entry.request_id);
+        assert_eq!("worker", work_entry.worker);
+        assert_eq!("desc", work_entry.description);
+        assert_eq!(1, work_entry.points_used);
+        assert_eq!(
+            chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            work_entry.work_date
+        );
+    }
+
+    #[test]
+    fn test_cumulative_usage() {
+        let ds = in_memory_datastore();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            12,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_customer("customer1").unwrap();
+        ds.add_request(1, "req1", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "req2", &"2025-12-31".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "req3", &"2026-12-31".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work1", 1, &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work1-2", 2, &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "alice", "work2", 3, &"2025-12-31".parse().unwrap())
+            .unwrap();
+        ds.add_work(3, "alice", "work3", 1, &"2026-12-31".parse().unwrap())
+            .unwrap();
+        let v = ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+        let v = v.cumulative_usage;
+        assert_eq!(v.len(), 3);
+        assert_eq!(v[0].cumulative_points_used, 1);
+        assert_eq!(v[1].cumulative_points_used, 3);
+        assert_eq!(v[2].cumulative_points_used, 6);
+    }
+
+    #[test]
+    fn test_search() {
+        let ds = in_memory_datastore();
+        ds.add_customer("Example Corp").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(
+            1,
+            "Renew the TLS certificate",
+            &"2025-03-01".parse().unwrap(),
+        )
+        .unwrap();
+        ds.add_request(1, "Replace disk", &"2025-04-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(
+            1,
+            "alice",
+            "Renewed certificates",
+            1,
+            &"2025-03-02".parse().unwrap(),
+        )
+        .unwrap();
+
+        let hits = ds.search("certificate", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert!(hits.iter().any(|h| h.entity == "request" && h.id == 1));
+        assert!(hits.iter().any(|h| h.entity == "work" && h.id == 1));
+        assert!(
+            hits.iter()
+                .all(|h| h.customer.as_deref() == Some("Example Corp"))
+        );
+        assert!(hits.iter().all(|h| h.snippet.contains('[')));
+
+        let hits = ds.search("example", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entity, "customer");
+        assert_eq!(hits[0].customer_id, Some(1));
+
+        let mut request = ds.get_request(2).unwrap();
+        request.description = "Replace certificate store".to_string();
+        ds.save_request(request).unwrap();
+        assert_eq!(ds.search("certificate", 10).unwrap().len(), 3);
+        assert!(ds.search("disk", 10).unwrap().is_empty());
+        assert!(ds.search("\"unbalanced", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_notes() {
+        let ds = in_memory_datastore();
+        ds.add_request(1, "desc", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "other", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        assert_eq!(1, ds.add_note(1, "alice", "first").unwrap());
+        ds.add_note(2, "bob", "elsewhere").unwrap();
+        ds.add_note(1, "bob", "second").unwrap();
+
+        let notes = ds.list_notes(1).unwrap();
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes[0].author, "alice");
+        assert_eq!(notes[0].body, "first");
+        assert_eq!(notes[1].author, "bob");
+        assert_eq!(notes[1].body, "second");
+        assert!(notes[0].created_at <= notes[1].created_at);
+    }
+
+    #[test]
+    fn test_attachments() {
+        let ds = in_memory_datastore();
+        ds.add_request(1, "desc", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "worker", "desc", 1, &"2025-01-01".parse().unwrap())
+            .unwrap();
+        assert_eq!(1, ds.add_attachment(1, None, "a.log", b"hello").unwrap());
+        ds.add_attachment(1, Some(1), "b.log", b"hello").unwrap();
+        ds.add_attachment(2, None, "c.log", b"other").unwrap();
+
+        let attachments = ds.list_attachments(1).unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].filename, "a.log");
+        assert_eq!(attachments[0].work_id, None);
+        assert_eq!(attachments[0].size, 5);
+        assert_eq!(attachments[1].work_id, Some(1));
+        assert_eq!(attachments[0].sha256, attachments[1].sha256);
+        assert_eq!(ds.attachment_data(2).unwrap(), b"hello");
+        assert_eq!(ds.get_attachment(3).unwrap().filename, "c.log");
+
+        let blobs: u32 = ds
+            .conn
+            .query_one("select count(*) from attachment_blob", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(blobs, 2);
+    }
+
+    #[test]
+    fn test_audit() {
+        let ds = in_memory_datastore();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            100,
+            Overlap::Reject,
+        )
+        .unwrap();
+        let mut contract = ds.get_contract(1).unwrap();
+        contract.total_points = 150;
+        ds.save_contract(contract, Overlap::Reject).unwrap();
+
+        let history = ds.history(Entity::Contract, 1).unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].action, "insert");
+        assert!(history[0].old_values.is_none());
+        assert_eq!(history[0].new_values.as_ref().unwrap()["total_points"], 100);
+        assert_eq!(history[1].action, "update");
+        assert_eq!(history[1].old_values.as_ref().unwrap()["total_points"], 100);
+        assert_eq!(history[1].new_values.as_ref().unwrap()["total_points"], 150);
+        assert_eq!(history[1].user, current_user());
+        assert!(ds.history(Entity::Customer, 1).unwrap().is_empty());
+
+        assert!(ds.conn.execute("delete from audit", []).is_err());
+        assert!(ds.conn.execute("update audit set user = 'x'", []).is_err());
+    }
+
+    #[test]
+    fn test_atomic_rolls_back() {
+        let ds = in_memory_datastore();
+        let result: Result<(), Error> = ds.atomic(|| {
+            ds.add_customer("customer1")?;
+            ds.conn
+                .execute("insert into no_such_table values (1)", [])?;
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert!(ds.list_customer().unwrap().is_empty());
+        assert!(ds.history(Entity::Customer, 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_undo() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            100,
+            Overlap::Reject,
+        )
+        .unwrap();
+        let mut contract = ds.get_contract(1).unwrap();
+        contract.total_points = 150;
+        ds.save_contract(contract, Overlap::Reject).unwrap();
+
+        let reverted = ds.undo(1).unwrap();
+        assert_eq!(reverted.len(), 1);
+        assert_eq!(reverted[0].action, "update");
+        assert_eq!(ds.get_contract(1).unwrap().total_points, 100);
+
+        let reverted = ds.undo(2).unwrap();
+        assert_eq!(reverted.len(), 2);
+        assert_eq!(reverted[0].entity, "contract");
+        assert_eq!(reverted[1].entity, "customer");
+        assert!(ds.list_contract().unwrap().is_empty());
+        assert!(ds.list_customer().unwrap().is_empty());
+        assert!(ds.search("customer1", 10).unwrap().is_empty());
+        assert!(ds.undo(1).unwrap().is_empty());
+
+        let history = ds.history(Entity::Contract, 1).unwrap();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[3].action, "delete");
+        assert_eq!(history[3].reverts, Some(history[0].id));
+        // Everything one call changed is undone together.
+        ds.add_customer("a").unwrap();
+        ds.atomic(|| {
+            ds.add_customer("b")?;
+            ds.add_customer("c")
+        })
+        .unwrap();
+        let reverted = ds.undo(1).unwrap();
+        assert_eq!(reverted.len(), 2);
+        assert_eq!(reverted[0].operation, reverted[1].operation);
+        assert_eq!(ds.list_customer().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_restores_deleted_row() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        let mut customer = ds.get_customer(1).unwrap();
+        customer.name = "renamed".to_string();
+        ds.save_customer(customer).unwrap();
+
+        let deleted = ds.snapshot(Entity::Customer, 1).unwrap();
+        ds.atomic(|| {
+            ds.conn.execute("DELETE FROM customer WHERE id = 1", [])?;
+            ds.append_audit(Entity::Customer, 1, "delete", deleted, None, None)
+        })
+        .unwrap();
+        assert!(ds.list_customer().unwrap().is_empty());
+
+        ds.undo(1).unwrap();
+        assert_eq!(ds.get_customer(1).unwrap().name, "renamed");
+        assert_eq!(ds.search("renamed", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_is_all_or_nothing() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        ds.add_customer("customer2").unwrap();
+        ds.conn
+            .execute("UPDATE customer SET name = 'changed' WHERE id = 1", [])
+            .unwrap();
+
+        assert!(matches!(ds.undo(2), Err(Error::Undo(_))));
+        assert_eq!(ds.list_customer().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req1", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work1", 1, &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work2", 2, &"2025-01-02".parse().unwrap())
+            .unwrap();
+
+        assert!(matches!(
+            ds.delete(Entity::Request, 1),
+            Err(Error::InUse { .. })
+        ));
+        assert!(matches!(
+            ds.delete(Entity::Note, 1),
+            Err(Error::NotTrashable(_))
+        ));
+
+        assert_eq!(1, ds.delete(Entity::Work, 2).unwrap());
+        assert_eq!(0, ds.delete(Entity::Work, 2).unwrap());
+        assert_eq!(ds.list_work().unwrap().len(), 1);
+        let usage = ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+        assert_eq!(usage.cumulative_usage.len(), 1);
+        assert!(ds.search("work2", 10).unwrap().is_empty());
+
+        let trash = ds.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].entity, "work");
+        assert_eq!(trash[0].summary, "work2");
+
+        assert_eq!(1, ds.restore(Entity::Work, 2).unwrap());
+        assert_eq!(ds.list_work().unwrap().len(), 2);
+        assert!(ds.list_trash().unwrap().is_empty());
+
+        let history = ds.history(Entity::Work, 2).unwrap();
+        let actions: Vec<_> = history.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["insert", "trash", "restore"]);
+
+        ds.undo(1).unwrap();
+        assert_eq!(ds.list_work().unwrap().len(), 1);
+        ds.undo(1).unwrap();
+        assert_eq!(ds.list_work().unwrap().len(), 2);
+        let history = ds.history(Entity::Work, 2).unwrap();
+        let actions: Vec<_> = history.iter().map(|e| e.action.as_str()).collect();
+        assert_eq!(actions, ["insert", "trash", "restore", "trash", "restore"]);
+    }
+
+    #[test]
+    fn test_restore_requires_live_parent() {
+        let ds = in_memory_datastore();
+        ds.add_request(1, "req1", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work1", 1, &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.delete(Entity::Work, 1).unwrap();
+        ds.delete(Entity::Request, 1).unwrap();
+        assert!(matches!(
+            ds.restore(Entity::Work, 1),
+            Err(Error::ParentDeleted { .. })
+        ));
+        ds.restore(Entity::Request, 1).unwrap();
+        ds.restore(Entity::Work, 1).unwrap();
+    }
+
+    #[test]
+    fn test_purge() {
+        let ds = in_memory_datastore();
+        ds.add_request(1, "req1", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "req2", &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "alice", "work1", 1, &"2025-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_note(1, "alice", "note").unwrap();
+        ds.add_attachment(1, None, "a.log", b"data").unwrap();
+        ds.delete(Entity::Request, 1).unwrap();
+        ds.delete(Entity::Work, 1).unwrap();
+
+        let purged = ds.purge("2000-01-01T00:00:00Z".parse().unwrap()).unwrap();
+        assert!(purged.is_empty());
+
+        let purged = ds.purge(Utc::now() + chrono::Duration::days(1)).unwrap();
+        assert_eq!(purged, [(Entity::Work, 1), (Entity::Request, 1)]);
+        assert!(ds.list_trash().unwrap().is_empty());
+        assert!(ds.get_request(1).is_err());
+        assert!(ds.list_notes(1).unwrap().is_empty());
+        assert!(ds.list_attachments(1).unwrap().is_empty());
+        // The history still refers to the attachment, so its data is kept.
+        let blobs: u32 = ds
+            .conn
+            .query_one("select count(*) from attachment_blob", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(blobs, 1);
+
+        // Undoing the purge brings back everything it removed.
+        let reverted = ds.undo(1).unwrap();
+        assert_eq!(reverted.len(), 4);
+        let trash = ds.list_trash().unwrap();
+        let mut trashed: Vec<_> = trash.iter().map(|t| t.entity.as_str()).collect();
+        trashed.sort();
+        assert_eq!(trashed, ["request", "work"]);
+        assert_eq!(ds.list_notes(1).unwrap().len(), 1);
+        assert_eq!(ds.list_attachments(1).unwrap().len(), 1);
+        assert_eq!(ds.attachment_data(1).unwrap(), b"data");
+
+        // Data no attachment or change refers to is removed.
+        ds.conn
+            .execute(
+                "INSERT INTO attachment_blob (sha256, data) VALUES ('orphan', x'00')",
+                [],
+            )
+            .unwrap();
+        ds.purge(Utc::now()).unwrap();
+        let blobs: u32 = ds
+            .conn
+            .query_one("select count(*) from attachment_blob", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(blobs, 1);
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_fields() {
+        assert!("name: x".parse::<Customer>().is_ok());
+        assert!("name: x\nnmae: y".parse::<Customer>().is_err());
+        assert!(
+            "customer_id: 1\nstart_date: 2025-01-01\nend_date: 2025-13-01\ntotal_points: 1"
+                .parse::<Contract>()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        let ds = in_memory_datastore();
+        assert!(matches!(
+            ds.add_customer("  "),
+            Err(Error::Validation(errors)) if errors == [FieldError::new("name", "must not be blank")]
+        ));
+
+        let result = ds.add_contract(
+            1,
+            &"2025-12-31".parse().unwrap(),
+            &"2025-01-01".parse().unwrap(),
+            0,
+            Overlap::Reject,
+        );
+        let Err(Error::Validation(errors)) = result else {
+            panic!("expected validation errors");
+        };
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["end_date", "total_points"]);
+        assert!(ds.list_contract().unwrap().is_empty());
+
+        ds.add_request(1, "req1", &"2025-02-01".parse().unwrap())
+            .unwrap();
+        let result = ds.add_work(1, "alice", "work1", 1, &"2025-01-31".parse().unwrap());
+        assert!(matches!(
+            result,
+            Err(Error::Validation(errors)) if errors[0].field == "work_date"
+        ));
+        ds.add_work(1, "alice", "work1", 1, &"2025-02-01".parse().unwrap())
+            .unwrap();
+
+        let mut work = ds.get_work(1).unwrap();
+        work.worker = String::new();
+        assert!(matches!(ds.save_work(work), Err(Error::Validation(_))));
+        assert_eq!(ds.get_work(1).unwrap().worker, "alice");
+    }
+
+    #[test]
+    fn test_overlapping_contracts() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        let add = |start: &str, end: &str, overlap| {
+            ds.add_contract(
+                1,
+                &start.parse().unwrap(),
+                &end.parse().unwrap(),
+                10,
+                overlap,
+            )
+        };
+        add("2025-01-01", "2025-06-30", Overlap::Reject).unwrap();
+        add("2025-07-01", "2025-12-31", Overlap::Reject).unwrap();
+        assert!(matches!(
+            add("2025-06-01", "2025-07-31", Overlap::Reject),
+            Err(Error::Overlap(ids)) if ids == [1, 2]
+        ));
+        assert!(ds.overlapping_contracts().unwrap().is_empty());
+
+        add("2025-06-01", "2025-07-31", Overlap::Allow).unwrap();
+        let overlaps = ds.overlapping_contracts().unwrap();
+        assert_eq!(overlaps.len(), 2);
+        assert_eq!(overlaps[0].first_contract_id, 1);
+        assert_eq!(overlaps[0].second_contract_id, 3);
+        assert_eq!(overlaps[0].from, "2025-06-01".parse().unwrap());
+        assert_eq!(overlaps[0].to, "2025-06-30".parse().unwrap());
+        assert_eq!(overlaps[0].customer.as_deref(), Some("customer1"));
+
+        let mut contract = ds.get_contract(2).unwrap();
+        contract.start_date = "2025-06-15".parse().unwrap();
+        assert!(matches!(
+            ds.save_contract(contract, Overlap::Reject),
+            Err(Error::Overlap(ids)) if ids == [1, 3]
+        ));
+        assert_eq!(
+            Error::Overlap(vec![1, 3]).to_string(),
+            "overlaps contracts 1, 3 of the same customer"
+        );
+        // Only a change of customer or period is checked again.
+        let mut contract = ds.get_contract(2).unwrap();
+        contract.total_points = 20;
+        ds.save_contract(contract, Overlap::Reject).unwrap();
+
+        ds.delete(Entity::Contract, 3).unwrap();
+        assert!(ds.overlapping_contracts().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_active_contract() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-06-30".parse().unwrap(),
+            2,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_contract(
+            1,
+            &"2025-07-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+
+        let date = "2025-03-01".parse().unwrap();
+        assert_eq!(ds.active_contract(1, date).unwrap().id, 1);
+        assert_eq!(
+            ds.active_contract(1, "2025-07-01".parse().unwrap())
+                .unwrap()
+                .id,
+            2
+        );
+        assert!(matches!(
+            ds.active_contract(1, "2026-01-01".parse().unwrap()),
+            Err(Error::NoActiveContract { .. })
+        ));
+        assert!(matches!(
+            ds.active_contract(2, date),
+            Err(Error::NoActiveContract { .. })
+        ));
+
+        ds.add_request(1, "req1", &date).unwrap();
+        ds.add_work(1, "alice", "work1", 2, &date).unwrap();
+        assert!(matches!(
+            ds.active_contract(1, date),
+            Err(Error::NoActiveContract { .. })
+        ));
+
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            5,
+            Overlap::Allow,
+        )
+        .unwrap();
+        assert_eq!(ds.active_contract(1, date).unwrap().id, 3);
+        assert!(matches!(
+            ds.active_contract(1, "2025-08-01".parse().unwrap()),
+            Err(Error::AmbiguousContract { contract_ids, .. }) if contract_ids == [2, 3]
+        ));
+    }
+
+    #[test]
+    fn test_unaccounted_work() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            12,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req1", &"2025-12-31".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "req2", &"2026-01-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work1", 1, &"2026-01-05".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "alice", "work2", 3, &"2026-01-05".parse().unwrap())
+            .unwrap();
+
+        assert!(
+            ds.outside_contract_period(1, "2025-12-31".parse().unwrap())
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            ds.outside_contract_period(1, "2026-01-01".parse().unwrap())
+                .unwrap()
+                .unwrap()
+                .id,
+            1
+        );
+
+        let unaccounted = ds.unaccounted_work().unwrap();
+        assert_eq!(unaccounted.len(), 1);
+        assert_eq!(unaccounted[0].work_id, 2);
+        assert_eq!(unaccounted[0].request_id, 2);
+        assert_eq!(unaccounted[0].points_used, 3);
+        assert_eq!(unaccounted[0].customer.as_deref(), Some("customer1"));
+
+        ds.delete(Entity::Work, 2).unwrap();
+        assert!(ds.unaccounted_work().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_customer_usage() {
+        let ds = in_memory_datastore();
+        ds.add_customer("customer1").unwrap();
+        ds.add_customer("customer2").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_contract(
+            1,
+            &"2024-01-01".parse().unwrap(),
+            &"2024-12-31".parse().unwrap(),
+            5,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_contract(
+            2,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            100,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req1", &"2025-02-01".parse().unwrap())
+            .unwrap();
+        ds.add_request(2, "req2", &"2024-02-01".parse().unwrap())
+            .unwrap();
+        ds.add_request(3, "req3", &"2025-02-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work1", 3, &"2025-02-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work2", 2, &"2025-03-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "alice", "work3", 6, &"2024-02-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(3, "alice", "work4", 50, &"2025-02-01".parse().unwrap())
+            .unwrap();
+
+        let usage = ds.customer_usage(1, "2025-02-15".parse().unwrap()).unwrap();
+        assert_eq!(usage.customer, "customer1");
+        assert_eq!(usage.contracts.len(), 2);
+        assert_eq!(usage.contracts[0].contract_id, 2);
+        assert_eq!(usage.contracts[0].points_used, 6);
+        assert_eq!(usage.contracts[0].points_remaining, -1);
+        assert_eq!(usage.contracts[1].contract_id, 1);
+        assert_eq!(usage.contracts[1].points_used, 3);
+        assert_eq!(usage.contracts[1].points_remaining, 7);
+        assert_eq!(usage.total_points, 15);
+        assert_eq!(usage.points_used, 9);
+        assert_eq!(usage.points_remaining, 6);
+    }
+
+    #[test]
+    fn test_dashboard() {
+        let ds = in_memory_datastore();
+        ds.add_customer("calm").unwrap();
+        ds.add_customer("busy").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            100,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_contract(
+            2,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_contract(
+            2,
+            &"2024-01-01".parse().unwrap(),
+            &"2024-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req1", &"2025-01-02".parse().unwrap())
+            .unwrap();
+        ds.add_request(2, "req2", &"2025-01-02".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "work1", 10, &"2025-01-02".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "alice", "work2", 5, &"2025-01-02".parse().unwrap())
+            .unwrap();
+
+        let entries = ds.dashboard("2025-07-02".parse().unwrap()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].contract_id, 2);
+        assert_eq!(entries[0].customer.as_deref(), Some("busy"));
+        assert_eq!(entries[0].points_used, 5);
+        assert_eq!(entries[0].burn_percent, 50.0);
+        assert_eq!(entries[0].days_remaining, 182);
+        assert_eq!(entries[0].status, BurnStatus::Ok);
+        assert_eq!(entries[1].contract_id, 1);
+        assert_eq!(entries[1].status, BurnStatus::Ok);
+
+        let entries = ds.dashboard("2025-01-31".parse().unwrap()).unwrap();
+        assert_eq!(entries[0].contract_id, 2);
+        assert_eq!(entries[0].status, BurnStatus::Critical);
+        assert_eq!(entries[1].status, BurnStatus::Ok);
+    }
+
+    #[test]
+    fn test_burn_status() {
+        assert_eq!(BurnStatus::new(50.0, 50.0), BurnStatus::Ok);
+        assert_eq!(BurnStatus::new(65.0, 50.0), BurnStatus::Warning);
+        assert_eq!(BurnStatus::new(80.0, 50.0), BurnStatus::Critical);
+        assert_eq!(BurnStatus::new(95.0, 99.0), BurnStatus::Critical);
+        assert_eq!(BurnStatus::new(100.0, 99.0), BurnStatus::Exhausted);
+    }
+
+    #[test]
+    fn test_grouped_usage() {
+        let ds = in_memory_datastore();
+        ds.add_customer("test").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            100,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req1", &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "req2", &"2025-02-10".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "w1", 1, &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "alice", "w2", 2, &"2025-02-12".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "bob", "w3", 4, &"2025-02-11".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "alice", "w4", 8, &"2025-04-01".parse().unwrap())
+            .unwrap();
+        let usage = ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+
+        let buckets = |by| {
+            usage
+                .grouped(by)
+                .buckets
+                .into_iter()
+                .map(|b| (b.bucket, b.points_used, b.cumulative_points_used))
+                .collect::<Vec<_>>()
+        };
+        let b = |s: &str, p, c| (s.to_string(), p, c);
+        assert_eq!(
+            buckets(GroupBy::Month),
+            vec![b("2025-01", 1, 1), b("2025-02", 6, 7), b("2025-04", 8, 15)]
+        );
+        assert_eq!(
+            buckets(GroupBy::Week),
+            vec![
+                b("2025-W02", 1, 1),
+                b("2025-W07", 6, 7),
+                b("2025-W14", 8, 15)
+            ]
+        );
+        assert_eq!(
+            buckets(GroupBy::Quarter),
+            vec![b("2025-Q1", 7, 7), b("2025-Q2", 8, 15)]
+        );
+        assert_eq!(
+            buckets(GroupBy::Request),
+            vec![b("1", 3, 3), b("2", 12, 15)]
+        );
+        assert_eq!(
+            buckets(GroupBy::Worker),
+            vec![b("alice", 10, 10), b("bob", 5, 15)]
+        );
+        assert_eq!(
+            usage.grouped(GroupBy::Request).buckets[1]
+                .description
+                .as_deref(),
+            Some("req2")
+        );
+
+        // Interleaved work on two requests of the same day.
+        ds.add_request(1, "req3", &"2025-05-01".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "req4", &"2025-05-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(3, "bob", "w5", 1, &"2025-05-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(4, "bob", "w6", 2, &"2025-05-02".parse().unwrap())
+            .unwrap();
+        ds.add_work(3, "bob", "w7", 4, &"2025-05-03".parse().unwrap())
+            .unwrap();
+        let usage = ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+        let buckets = usage
+            .grouped(GroupBy::Request)
+            .buckets
+            .into_iter()
+            .map(|b| (b.bucket, b.points_used, b.cumulative_points_used))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            buckets,
+            vec![b("1", 3, 3), b("2", 12, 15), b("3", 5, 20), b("4", 2, 22)]
+        );
+    }
+
+    #[test]
+    fn test_usage_between() {
+        let ds = in_memory_datastore();
+        ds.add_customer("test").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            100,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req1", &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "jan", 1, &"2025-01-31".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "feb", 2, &"2025-02-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "feb", 4, &"2025-02-28".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "mar", 8, &"2025-03-01".parse().unwrap())
+            .unwrap();
+
+        let s = ds
+            .usage_between(
+                1,
+                "2025-02-01".parse().unwrap(),
+                "2025-02-28".parse().unwrap(),
+            )
+            .unwrap();
+        assert_eq!(s.opening_points_used, 1);
+        assert_eq!(s.opening_balance, 99);
+        assert_eq!(s.points_used, 6);
+        assert_eq!(s.closing_balance, 93);
+        assert_eq!(s.cumulative_usage.len(), 2);
+        assert_eq!(s.cumulative_usage[1].cumulative_points_used, 7);
+
+        assert!(matches!(
+            ds.usage_between(
+                1,
+                "2025-03-01".parse().unwrap(),
+                "2025-02-01".parse().unwrap()
+            ),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn test_remaining_by_date() {
+        let ds = in_memory_datastore();
+        ds.add_customer("test").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req1", &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "req2", &"2025-01-11".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "bob", "w1", 1, &"2025-01-11".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "w2", 2, &"2025-02-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(2, "bob", "w3", 4, &"2025-02-01".parse().unwrap())
+            .unwrap();
+
+        let usage = ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+        assert_eq!(
+            usage.remaining_by_date(),
+            vec![
+                ("2025-01-11".parse().unwrap(), 9),
+                ("2025-02-01".parse().unwrap(), 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contract_report() {
+        let ds = in_memory_datastore();
+        ds.add_customer("test").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "worked", &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "open", &"2025-01-11".parse().unwrap())
+            .unwrap();
+        ds.add_request(1, "late", &"2026-01-11".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "w1", 2, &"2025-01-11".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "w2", 3, &"2026-06-01".parse().unwrap())
+            .unwrap();
+        ds.add_work(3, "bob", "w3", 4, &"2026-01-20".parse().unwrap())
+            .unwrap();
 
-        Ok(rows)
+        let report = ds
+            .contract_report(1, "2026-02-01".parse().unwrap())
+            .unwrap();
+        assert_eq!(report.customer, "test");
+        assert_eq!(report.contract.points_used, 2);
+        let requests: Vec<_> = report
+            .requests
+            .iter()
+            .map(|r| (r.description.as_str(), r.status, r.points_used))
+            .collect();
+        assert_eq!(
+            requests,
+            vec![
+                ("worked", RequestStatus::Worked, 2),
+                ("open", RequestStatus::Open, 0),
+                ("late", RequestStatus::Unaccounted, 4)
+            ]
+        );
     }
 
-    pub fn save_contract(&self, entity: Contract) -> Result<usize, rusqlite::Error> {
-        let rows = self.conn.execute(
-            "UPDATE contract SET
-                customer_id = :customer_id,
-                start_date = :start_date,
-                end_date = :end_date,
-                total_points = :total_points
-            WHERE
-                id = :id",
-            &[
-                (":customer_id", &entity.customer_id.to_string()),
-                (":start_date", &entity.start_date.to_string()),
-                (":end_date", &entity.end_date.to_string()),
-                (":total_points", &entity.total_points.to_string()),
-                (":id", &entity.id.to_string()),
-            ],
-        )?;
-
-        Ok(rows)
-    }
+    #[test]
+    fn test_batch() {
+        let ds = in_memory_datastore();
+        ds.batch(true, &mut || ds.add_customer("dry").map(|_| ()))
+            .unwrap();
+        assert_eq!(ds.list_customer().unwrap().len(), 0);
+        assert_eq!(ds.history(Entity::Customer, 1).unwrap().len(), 0);
 
-    pub fn save_request(&self, entity: Request) -> Result<usize, rusqlite::Error> {
-        let rows = self.conn.execute(
-            "UPDATE request SET
-                contract_id = :contract_id,
-                description = :description,
-                request_date = :request_date
-            WHERE
-                id = :id",
-            &[
-                (":contract_id", &entity.contract_id.to_string()),
-                (":description", &entity.description),
-                (":request_date", &entity.request_date.to_string()),
-                (":id", &entity.id.to_string()),
-            ],
-        )?;
+        let result = ds.batch(false, &mut || {
+            ds.add_customer("kept?")?;
+            ds.add_customer("")?;
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::Validation(_))));
+        assert_eq!(ds.list_customer().unwrap().len(), 0);
 
-        Ok(rows)
+        ds.batch(false, &mut || {
+            ds.add_customer("one")?;
+            ds.add_customer("two")?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(ds.list_customer().unwrap().len(), 2);
     }
 
-    pub fn save_work(&self, entity: Work) -> Result<usize, rusqlite::Error> {
-        let rows = self.conn.execute(
-            "UPDATE work SET
-                request_id = :request_id,
-                worker = :worker,
-                description = :description,
-                points_used = :points_used,
-                work_date = :work_date
-            WHERE
-                id = :id",
-            &[
-                (":request_id", &entity.request_id.to_string()),
-                (":worker", &entity.worker),
-                (":description", &entity.description),
-                (":points_used", &entity.points_used.to_string()),
-                (":work_date", &entity.work_date.to_string()),
-                (":id", &entity.id.to_string()),
-            ],
-        )?;
+    #[test]
+    fn test_export_import() {
+        let ds = in_memory_datastore();
+        ds.add_customer("gone").unwrap();
+        ds.add_customer("test").unwrap();
+        ds.add_contract(
+            2,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req", &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "kept", 2, &"2025-01-11".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "trashed", 3, &"2025-01-11".parse().unwrap())
+            .unwrap();
+        ds.delete(Entity::Work, 2).unwrap();
+        ds.delete(Entity::Customer, 1).unwrap();
+        let export = ds.export().unwrap();
+        assert_eq!(export.version, EXPORT_VERSION);
+        assert_eq!(export.customers.len(), 1);
+        assert_eq!(export.work.len(), 1);
 
-        Ok(rows)
-    }
+        let restored = in_memory_datastore();
+        let summary = restored.import(&export, false, Overlap::Reject).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                customers: 1,
+                contracts: 1,
+                requests: 1,
+                work: 1
+            }
+        );
+        assert_eq!(restored.export().unwrap(), export);
+        assert!(matches!(
+            restored.import(&export, false, Overlap::Reject),
+            Err(Error::NotEmpty)
+        ));
 
-    pub fn usage(&self, contract_id: u32, date: NaiveDate) -> Result<ContractUsage, Error> {
-        let contract = self.get_contract(contract_id)?;
+        restored.import(&export, true, Overlap::Reject).unwrap();
+        let merged = restored.export().unwrap();
+        assert_eq!(merged.customers.len(), 2);
+        assert_eq!(merged.contracts[1].id, 2);
+        assert_eq!(merged.contracts[1].customer_id, 3);
+        assert_eq!(merged.requests[1].contract_id, 2);
+        assert_eq!(merged.work[1].request_id, 2);
 
-        let mut stmt = self.conn.prepare(
-            "
-            SELECT
-                request.request_date,
-                request.description,
-                work.worker,
-                work.work_date,
-                work.description,
-                work.points_used,
-                SUM(work.points_used)
-                    OVER (
-                        PARTITION BY contract.id
-                        ORDER BY request.request_date, work.work_date
-                        ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
-                    ) AS cumulative_points_used
-            FROM
-                contract
-                INNER JOIN request ON contract.id = request.contract_id
-                INNER JOIN work ON request.id = work.request_id
-            WHERE
-                contract.id = :id
-                AND request.request_date BETWEEN contract.start_date and contract.end_date
-                AND work.work_date <= :date
-            ",
-        )?;
+        let mut broken = ds.export().unwrap();
+        broken.requests[0].contract_id = 99;
+        let fresh = in_memory_datastore();
+        match fresh.import(&broken, false, Overlap::Reject) {
+            Err(Error::Rows(errors)) => {
+                assert_eq!(errors.len(), 2);
+                assert_eq!(errors[0].section, Some("requests"));
+                assert_eq!(errors[0].row, 1);
+                assert_eq!(errors[1].section, Some("work"));
+            }
+            other => panic!("expected row errors, got {:?}", other),
+        }
+        assert_eq!(fresh.list_customer().unwrap().len(), 0);
 
-        let rows = stmt.query_map(
-            named_params! {":id": contract_id, ":date": date.to_string() },
-            |r| CumulativeUsage::try_from(r),
-        )?;
-        let mut results = Vec::new();
-        for r in rows {
-            results.push(r?);
+        // Records saved before today's checks existed restore as they are,
+        // but are checked when merged.
+        let mut old = ds.export().unwrap();
+        old.customers[0].name = " ".to_string();
+        old.work[0].work_date = "2025-01-01".parse().unwrap();
+        let fresh = in_memory_datastore();
+        fresh.import(&old, false, Overlap::Reject).unwrap();
+        assert_eq!(fresh.export().unwrap(), old);
+        match fresh.import(&old, true, Overlap::Reject) {
+            Err(Error::Rows(errors)) => {
+                assert_eq!(errors.len(), 4);
+                assert_eq!(
+                    errors[0].to_string(),
+                    "customers row 1: customer 2: invalid record: name: must not be blank"
+                );
+            }
+            other => panic!("expected row errors, got {:?}", other),
         }
 
-        let contract_usage = ContractUsage {
-            start_date: contract.start_date,
-            end_date: contract.end_date,
-            total_points: contract.total_points,
-            cumulative_usage: results,
+        // Merged contracts may not overlap others of their customer unless
+        // allowed to, like added ones.
+        let mut overlapping = ds.export().unwrap();
+        let first = &overlapping.contracts[0];
+        let contract = ExportedContract {
+            id: 2,
+            customer_id: first.customer_id,
+            start_date: first.start_date,
+            end_date: first.end_date,
+            total_points: first.total_points,
         };
+        overlapping.contracts.push(contract);
+        let fresh = in_memory_datastore();
+        match fresh.import(&overlapping, true, Overlap::Reject) {
+            Err(Error::Rows(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].section, Some("contracts"));
+                assert_eq!(errors[0].row, 2);
+                assert!(errors[0].message.contains("overlaps contract 1"));
+            }
+            other => panic!("expected row errors, got {:?}", other),
+        }
+        assert_eq!(fresh.list_contract().unwrap().len(), 0);
+        fresh.import(&overlapping, true, Overlap::Allow).unwrap();
+        assert_eq!(fresh.list_contract().unwrap().len(), 2);
 
-        Ok(contract_usage)
+        broken.version = EXPORT_VERSION + 1;
+        assert!(matches!(
+            fresh.import(&broken, false, Overlap::Reject),
+            Err(Error::Unsupported(_))
+        ));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_dir_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("records");
 
-    fn in_memory_datastore() -> DataStore {
-        let conn = Connection::open_in_memory().unwrap();
-        DataStore::init(&conn).unwrap();
-        DataStore { conn }
-    }
+        let ds = DataStore::open_dir(&root).unwrap();
+        ds.add_customer("test").unwrap();
+        ds.add_customer("gone").unwrap();
+        ds.add_contract(
+            1,
+            &"2025-01-01".parse().unwrap(),
+            &"2025-12-31".parse().unwrap(),
+            10,
+            Overlap::Reject,
+        )
+        .unwrap();
+        ds.add_request(1, "req", &"2025-01-10".parse().unwrap())
+            .unwrap();
+        ds.add_work(1, "bob", "work", 2, &"2025-01-11".parse().unwrap())
+            .unwrap();
+        ds.add_note(1, "bob", "note").unwrap();
+        ds.add_attachment(1, None, "log.txt", b"data").unwrap();
+        ds.delete(Entity::Customer, 2).unwrap();
+        assert!(!root.exists());
+        ds.flush().unwrap();
 
-    #[test]
-    fn test_init() {
-        let ds = in_memory_datastore();
-        assert!(ds.conn.table_exists(Some("main"), "customer").unwrap());
-        assert!(ds.conn.table_exists(Some("main"), "contract").unwrap());
-        assert!(ds.conn.table_exists(Some("main"), "request").unwrap());
-        assert!(ds.conn.table_exists(Some("main"), "work").unwrap());
-    }
+        assert_eq!(
+            std::fs::read_to_string(root.join("customer/1.yaml")).unwrap(),
+            "name: test\n"
+        );
+        assert!(root.join("trash/customer/2.yaml").exists());
+        assert!(root.join("work/1.yaml").exists());
 
-    #[test]
-    fn test_customer() {
-        let ds = in_memory_datastore();
-        let nrow = ds.add_customer("test customer").unwrap();
-        assert_eq!(1, nrow);
-        let customers = ds.list_customer().unwrap();
-        let customer = &customers[0];
-        assert_eq!(1, customer.id);
-        assert_eq!("test customer", customer.name);
+        let reopened = DataStore::open_dir(&root).unwrap();
+        assert_eq!(reopened.export().unwrap(), ds.export().unwrap());
+        assert_eq!(reopened.list_trash().unwrap().len(), 1);
+        assert_eq!(reopened.list_notes(1).unwrap()[0].body, "note");
+        let attachment = &reopened.list_attachments(1).unwrap()[0];
+        assert_eq!(reopened.attachment_data(attachment.id).unwrap(), b"data");
+        assert_eq!(reopened.history(Entity::Work, 1).unwrap().len(), 1);
+        let usage = reopened.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+        assert_eq!(ContractSummary::from(&usage).points_used, 2);
+
+        reopened
+            .purge(Utc::now() + chrono::Duration::days(1))
+            .unwrap();
+        reopened.flush().unwrap();
+        assert!(!root.join("trash/customer/2.yaml").exists());
+
+        // The id of the purged customer is not handed out again, so its
+        // history stays its own, also in trees written without sequence.yaml.
+        let add_customer = || {
+            let reopened = DataStore::open_dir(&root).unwrap();
+            reopened.add_customer("new").unwrap();
+            assert_eq!(reopened.list_customer().unwrap()[1].id, 3);
+            assert_eq!(reopened.history(Entity::Customer, 3).unwrap().len(), 1);
+        };
+        add_customer();
+        std::fs::remove_file(root.join("sequence.yaml")).unwrap();
+        add_customer();
+
+        // A record that fails the checks still opens, so it can be fixed.
+        std::fs::write(root.join("customer/3.yaml"), "name: ''\n").unwrap();
+        let reopened = DataStore::open_dir(&root).unwrap();
+        assert_eq!(reopened.get_customer(3).unwrap().name, "");
+
+        std::fs::write(root.join("customer/3.yaml"), "name: x\nextra: 1\n").unwrap();
+        assert!(matches!(
+            DataStore::open_dir(&root),
+            Err(Error::Format { .. })
+        ));
     }
 
-    #[test]
-    fn test_contract() {
-        let ds = in_memory_datastore();
-        let nrow = ds
+    /// The same few records in any store, for comparing stores.
+    fn fill_store(store: &dyn Store) {
+        store.add_customer("test").unwrap();
+        store
             .add_contract(
                 1,
                 &"2025-01-01".parse().unwrap(),
                 &"2025-12-31".parse().unwrap(),
                 10,
+                Overlap::Reject,
             )
             .unwrap();
-        assert_eq!(1, nrow);
-        let contracts = ds.list_contract().unwrap();
-        let contract = &contracts[0];
-        assert_eq!(1, contract.id);
-        assert_eq!(1, contract.customer_id);
+        store
+            .add_contract(
+                1,
+                &"2025-06-01".parse().unwrap(),
+                &"2026-05-31".parse().unwrap(),
+                20,
+                Overlap::Allow,
+            )
+            .unwrap();
+        store
+            .add_request(1, "first", &"2025-02-01".parse().unwrap())
+            .unwrap();
+        store
+            .add_request(1, "late", &"2026-01-15".parse().unwrap())
+            .unwrap();
+        store
+            .add_work(1, "me", "a", 3, &"2025-02-03".parse().unwrap())
+            .unwrap();
+        store
+            .add_work(1, "me", "b", 2, &"2025-02-02".parse().unwrap())
+            .unwrap();
+        store
+            .add_work(2, "me", "c", 4, &"2026-01-16".parse().unwrap())
+            .unwrap();
+    }
+
+    fn yaml<T: Serialize>(value: T) -> String {
+        serde_yaml::to_string(&value).unwrap()
+    }
+
+    /// Fails unless `store`, filled by `fill_store`, reports what `ds` does.
+    fn assert_same_reports(store: &dyn Store, ds: &DataStore) {
+        let date = "2025-07-01".parse().unwrap();
         assert_eq!(
-            chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
-            contract.start_date
+            store.usage(1, date).unwrap().to_string(),
+            ds.usage(1, date).unwrap().to_string()
         );
+        assert_eq!(store.export().unwrap(), ds.export().unwrap());
         assert_eq!(
-            chrono::NaiveDate::from_ymd_opt(2025, 12, 31).unwrap(),
-            contract.end_date
+            yaml(store.dashboard(date).unwrap()),
+            yaml(ds.dashboard(date).unwrap())
         );
-        assert_eq!(10, contract.total_points);
+        assert_eq!(
+            yaml(store.customer_usage(1, date).unwrap()),
+            yaml(ds.customer_usage(1, date).unwrap())
+        );
+        assert_eq!(
+            yaml(store.contract_report(1, date).unwrap()),
+            yaml(ds.contract_report(1, date).unwrap())
+        );
+        assert_eq!(
+            yaml(store.overlapping_contracts().unwrap()),
+            yaml(ds.overlapping_contracts().unwrap())
+        );
+        assert_eq!(
+            yaml(store.unaccounted_work().unwrap()),
+            yaml(ds.unaccounted_work().unwrap())
+        );
+    }
+
+    /// Fails unless `store`, filled by `fill_store`, reports ids it does not
+    /// have as `Error::NotFound`.
+    fn assert_missing_ids(store: &dyn Store) {
+        assert!(matches!(
+            store.get_customer(99),
+            Err(Error::NotFound(Entity::Customer, 99))
+        ));
+        assert!(matches!(
+            store.get_contract(99),
+            Err(Error::NotFound(Entity::Contract, 99))
+        ));
+        assert!(matches!(
+            store.get_request(99),
+            Err(Error::NotFound(Entity::Request, 99))
+        ));
+        assert!(matches!(
+            store.get_work(99),
+            Err(Error::NotFound(Entity::Work, 99))
+        ));
+        assert!(matches!(
+            store.delete(Entity::Work, 99),
+            Err(Error::NotFound(Entity::Work, 99))
+        ));
+        // Stores without attachments say so instead.
+        for result in [
+            store.get_attachment(99).map(|_| ()),
+            store.attachment_data(99).map(|_| ()),
+        ] {
+            assert!(matches!(
+                result,
+                Err(Error::NotFound(Entity::Attachment, 99) | Error::Unsupported(_))
+            ));
+        }
     }
 
     #[test]
-    fn test_request() {
+    fn test_memory_store() {
         let ds = in_memory_datastore();
-        let nrow = ds
-            .add_request(1, "desc", &"2025-01-01".parse().unwrap())
+        let ms = MemoryStore::new();
+        fill_store(&ds);
+        fill_store(&ms);
+        assert_same_reports(&ms, &ds);
+        assert_missing_ids(&ds);
+        assert_missing_ids(&ms);
+
+        let date = "2025-07-01".parse().unwrap();
+        assert!(matches!(
+            ms.active_contract(1, date),
+            Err(Error::AmbiguousContract { .. })
+        ));
+
+        assert!(matches!(
+            ms.add_contract(1, &date, &date, 1, Overlap::Reject),
+            Err(Error::Overlap(ids)) if ids == vec![1, 2]
+        ));
+        assert!(matches!(
+            ms.add_work(1, "me", "early", 1, &"2025-01-01".parse().unwrap()),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            ms.delete(Entity::Request, 1),
+            Err(Error::InUse { child_id: 1, .. })
+        ));
+        assert!(matches!(ms.list_trash(), Err(Error::Unsupported(_))));
+
+        let result = ms.batch(false, &mut || {
+            ms.delete(Entity::Work, 3)?;
+            ms.delete(Entity::Request, 2)?;
+            ms.delete(Entity::Customer, 1)?;
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::InUse { .. })));
+        assert_eq!(ms.list_work().unwrap().len(), 3);
+        ms.batch(true, &mut || ms.delete(Entity::Work, 3).map(|_| ()))
             .unwrap();
-        assert_eq!(1, nrow);
-        let requests = ds.list_request().unwrap();
-        let request = &requests[0];
-        assert_eq!(1, request.id);
-        assert_eq!(1, request.contract_id);
-        assert_eq!("desc", request.description);
-        assert_eq!(
-            chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
-            request.request_date
-        );
+        assert_eq!(ms.list_work().unwrap().len(), 3);
+        assert_eq!(ms.delete(Entity::Work, 3).unwrap(), 1);
+        assert_eq!(ms.list_work().unwrap().len(), 2);
+    }
+
+    /// A PostgreSQL server of its own in a temporary directory, stopped when
+    /// dropped. Needs `initdb` and `pg_ctl` on the `PATH`.
+    #[cfg(feature = "postgres")]
+    struct TempPostgres {
+        dir: tempfile::TempDir,
+        port: u16,
+    }
+
+    #[cfg(feature = "postgres")]
+    impl TempPostgres {
+        fn start() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let data = dir.path().join("data");
+            let status = std::process::Command::new("initdb")
+                .args(["-A", "trust", "-U", "maint", "--no-sync", "-D"])
+                .arg(&data)
+                .stdout(std::process::Stdio::null())
+                .status()
+                .expect("initdb should be on the PATH");
+            assert!(status.success(), "initdb failed");
+            // A port nobody listens on right now.
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let options = format!(
+                "-p {} -k {} -c listen_addresses=127.0.0.1 -c fsync=off",
+                port,
+                dir.path().display()
+            );
+            let status = std::process::Command::new("pg_ctl")
+                .args(["-w", "-o", &options, "-l"])
+                .arg(dir.path().join("log"))
+                .arg("-D")
+                .arg(&data)
+                .arg("start")
+                .stdout(std::process::Stdio::null())
+                .status()
+                .expect("pg_ctl should be on the PATH");
+            assert!(status.success(), "pg_ctl start failed");
+            Self { dir, port }
+        }
+
+        fn url(&self) -> String {
+            format!("postgres://maint@127.0.0.1:{}/postgres", self.port)
+        }
+
+        /// The URL of a new, empty database on the server.
+        fn create_database(&self, name: &str) -> String {
+            let mut client = ::postgres::Client::connect(&self.url(), ::postgres::NoTls).unwrap();
+            client
+                .batch_execute(&format!("CREATE DATABASE {}", name))
+                .unwrap();
+            format!("postgres://maint@127.0.0.1:{}/{}", self.port, name)
+        }
     }
 
+    #[cfg(feature = "postgres")]
+    impl Drop for TempPostgres {
+        fn drop(&mut self) {
+            let _ = std::process::Command::new("pg_ctl")
+                .args(["-m", "immediate", "-D"])
+                .arg(self.dir.path().join("data"))
+                .arg("stop")
+                .stdout(std::process::Stdio::null())
+                .status();
+        }
+    }
+
+    #[cfg(feature = "postgres")]
     #[test]
-    fn test_work() {
+    #[ignore = "starts a PostgreSQL server, so needs initdb and pg_ctl on the PATH and a \
+                user other than root; run with --features postgres -- --ignored"]
+    fn test_postgres_store() {
+        let server = TempPostgres::start();
+        let url = server.url();
+
         let ds = in_memory_datastore();
-        let nrow = ds
-            .add_work(1, "worker", "desc", 1, &"2025-01-01".parse().unwrap())
+        let pg = PgStore::connect(&url).unwrap();
+        fill_store(&ds);
+        fill_store(&pg);
+        assert_same_reports(&pg, &ds);
+        assert_missing_ids(&pg);
+        // Connecting again finds the schema in place.
+        let pg = PgStore::connect(&url).unwrap();
+        assert_eq!(pg.export().unwrap(), ds.export().unwrap());
+
+        let date = "2025-07-01".parse().unwrap();
+        assert!(matches!(
+            pg.add_contract(1, &date, &date, 1, Overlap::Reject),
+            Err(Error::Overlap(ids)) if ids == vec![1, 2]
+        ));
+        assert!(matches!(
+            pg.add_work(1, "me", "early", 1, &"2025-01-01".parse().unwrap()),
+            Err(Error::Validation(_))
+        ));
+
+        let result = pg.batch(false, &mut || {
+            pg.delete(Entity::Work, 3)?;
+            pg.delete(Entity::Request, 1)?;
+            Ok(())
+        });
+        assert!(matches!(result, Err(Error::InUse { child_id: 1, .. })));
+        pg.batch(true, &mut || pg.delete(Entity::Work, 3).map(|_| ()))
             .unwrap();
-        assert_eq!(1, nrow);
-        let work_entries = ds.list_work().unwrap();
-        let work_entry = &work_entries[0];
-        assert_eq!(1, work_entry.id);
-        assert_eq!(1, work_entry.request_id);
-        assert_eq!("worker", work_entry.worker);
-        assert_eq!("desc", work_entry.description);
-        assert_eq!(1, work_entry.points_used);
+        assert_eq!(pg.list_work().unwrap().len(), 3);
+
+        pg.delete(Entity::Work, 3).unwrap();
+        pg.delete(Entity::Request, 2).unwrap();
+        assert_eq!(pg.usage(1, date).unwrap().cumulative_usage.len(), 2);
+        assert_eq!(pg.list_trash().unwrap().len(), 2);
+        assert!(matches!(
+            pg.restore(Entity::Work, 3),
+            Err(Error::ParentDeleted { parent_id: 2, .. })
+        ));
+        pg.restore(Entity::Request, 2).unwrap();
+        pg.restore(Entity::Work, 3).unwrap();
+        assert_eq!(pg.export().unwrap(), ds.export().unwrap());
+
+        // An export moves onto the server with its ids, and merges like into
+        // SQLite.
+        let export = ds.export().unwrap();
+        let restored = PgStore::connect(&server.create_database("restored")).unwrap();
+        restored.import(&export, false, Overlap::Reject).unwrap();
+        assert_eq!(restored.export().unwrap(), export);
+        assert!(matches!(
+            restored.import(&export, false, Overlap::Reject),
+            Err(Error::NotEmpty)
+        ));
+        restored.add_customer("next").unwrap();
+        assert_eq!(restored.get_customer(2).unwrap().name, "next");
+        let merged = in_memory_datastore();
+        fill_store(&merged);
+        merged.add_customer("next").unwrap();
+        let errors = |result: Result<ImportSummary, Error>| match result {
+            Err(Error::Rows(errors)) => errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            other => panic!("expected row errors, got {:?}", other),
+        };
+        let expected = errors(merged.import(&export, true, Overlap::Reject));
+        assert!(expected[0].starts_with("contracts row 2: "));
         assert_eq!(
-            chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
-            work_entry.work_date
+            errors(restored.import(&export, true, Overlap::Reject)),
+            expected
+        );
+        assert_eq!(
+            restored.import(&export, true, Overlap::Allow).unwrap(),
+            merged.import(&export, true, Overlap::Allow).unwrap()
+        );
+        assert_eq!(restored.list_customer().unwrap().len(), 3);
+
+        for store in [&ds as &dyn Store, &pg] {
+            store.add_note(1, "me", "called back").unwrap();
+            store
+                .add_attachment(1, Some(1), "log.txt", b"data")
+                .unwrap();
+            store
+                .save_customer(Customer {
+                    id: 1,
+                    name: "renamed".to_string(),
+                })
+                .unwrap();
+        }
+        assert_eq!(pg.list_notes(1).unwrap()[0].body, "called back");
+        assert_eq!(pg.list_attachments(1).unwrap()[0].size, 4);
+        assert_eq!(pg.attachment_data(1).unwrap(), b"data");
+        let changes = |store: &dyn Store| {
+            store
+                .history(Entity::Customer, 1)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.action, e.old_values, e.new_values))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(changes(&pg), changes(&ds));
+
+        let hits = pg.search("renamed", 10).unwrap();
+        assert_eq!((hits[0].entity.as_str(), hits[0].id), ("customer", 1));
+        assert_eq!(hits[0].snippet, "[renamed]");
+        let hits = pg.search("first", 10).unwrap();
+        assert_eq!(
+            (hits[0].entity.as_str(), hits[0].customer_id),
+            ("request", Some(1))
         );
+        assert!(pg.search(" ", 10).unwrap().is_empty());
+
+        pg.delete(Entity::Work, 1).unwrap();
+        assert_eq!(pg.purge(Utc::now()).unwrap(), vec![(Entity::Work, 1)]);
+        assert!(matches!(
+            pg.attachment_data(1),
+            Err(Error::NotFound(Entity::Attachment, 1))
+        ));
+        pg.undo(2).unwrap();
+        assert_eq!(pg.attachment_data(1).unwrap(), b"data");
+        assert_eq!(pg.list_work().unwrap().len(), 3);
+        assert!(pg.undo(1).unwrap().iter().all(|e| e.entity == "customer"));
+        assert_eq!(pg.get_customer(1).unwrap().name, "test");
     }
 
     #[test]
-    fn test_cumulative_usage() {
-        let ds = in_memory_datastore();
-        ds.add_contract(
-            1,
-            &"2025-01-01".parse().unwrap(),
-            &"2025-12-31".parse().unwrap(),
-            12,
-        )
-        .unwrap();
-        ds.add_customer("customer1").unwrap();
-        ds.add_request(1, "req1", &"2025-01-01".parse().unwrap())
-            .unwrap();
-        ds.add_request(1, "req2", &"2025-12-31".parse().unwrap())
+    fn test_concurrent_writers() {
+        const THREADS: usize = 8;
+        const WRITES: usize = 25;
+
+        // Each thread opens its own connection, like separate `maint` runs do.
+        fn hammer(path: &Path, journal: Journal, f: impl Fn(&DataStore, usize) + Sync) {
+            let barrier = std::sync::Barrier::new(THREADS);
+            std::thread::scope(|scope| {
+                for thread in 0..THREADS {
+                    let (barrier, f) = (&barrier, &f);
+                    scope.spawn(move || {
+                        barrier.wait();
+                        let ds = DataStore::open_with_journal(path, journal).unwrap();
+                        for i in 0..WRITES {
+                            f(&ds, thread * WRITES + i);
+                        }
+                    });
+                }
+            });
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        for (journal, expected) in [(Journal::Rollback, "delete"), (Journal::Wal, "wal")] {
+            let path = dir.path().join(format!("{:?}.db", journal));
+
+            // Everyone migrates the new file at once.
+            hammer(&path, journal, |ds, i| {
+                ds.add_customer(&format!("customer {}", i)).unwrap();
+            });
+            let ds = DataStore::open_with_journal(&path, journal).unwrap();
+            assert_eq!(ds.list_customer().unwrap().len(), THREADS * WRITES);
+            let mode: String = ds
+                .conn
+                .pragma_query_value(None, "journal_mode", |r| r.get(0))
+                .unwrap();
+            assert_eq!(mode, expected);
+
+            ds.add_contract(
+                1,
+                &"2025-01-01".parse().unwrap(),
+                &"2025-12-31".parse().unwrap(),
+                1000,
+                Overlap::Reject,
+            )
             .unwrap();
-        ds.add_request(1, "req3", &"2026-12-31".parse().unwrap())
+            ds.add_request(1, "busy", &"2025-01-01".parse().unwrap())
+                .unwrap();
+            hammer(&path, journal, |ds, i| {
+                ds.add_work(
+                    1,
+                    "me",
+                    &format!("work {}", i),
+                    1,
+                    &"2025-01-02".parse().unwrap(),
+                )
+                .unwrap();
+                ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+            });
+            let usage = ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
+            assert_eq!(
+                ContractSummary::from(&usage).points_used as usize,
+                THREADS * WRITES
+            );
+            assert_eq!(ds.undo(1).unwrap().len(), 1);
+            assert_eq!(ds.list_work().unwrap().len(), THREADS * WRITES - 1);
+        }
+
+        // A file left in WAL mode goes back to the rollback journal once it
+        // is opened without asking for WAL.
+        let ds = DataStore::open(dir.path().join("Wal.db")).unwrap();
+        let mode: String = ds
+            .conn
+            .pragma_query_value(None, "journal_mode", |r| r.get(0))
             .unwrap();
-        ds.add_work(1, "alice", "work1", 1, &"2025-01-01".parse().unwrap())
+        assert_eq!(mode, "delete");
+    }
+
+    #[test]
+    fn test_backup_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("maint.db");
+        let backups = dir.path().join("maint.db.backups");
+        let count = |dir: &Path| std::fs::read_dir(dir).unwrap().count();
+
+        let ds = DataStore::open(&path).unwrap();
+        ds.add_customer("before").unwrap();
+        let first = ds.backup(None, 2).unwrap();
+        assert_eq!(first.parent().unwrap(), backups);
+        ds.backup(None, 2).unwrap();
+        let last = ds.backup(None, 2).unwrap();
+        assert_eq!(count(&backups), 2);
+        assert!(!first.exists());
+
+        ds.add_customer("after").unwrap();
+        let saved = ds.restore_backup(&last).unwrap();
+        assert_eq!(ds.list_customer().unwrap().len(), 1);
+        assert_eq!(count(&backups), 3);
+        let saved = DataStore::open(&saved).unwrap();
+        assert_eq!(saved.list_customer().unwrap().len(), 2);
+
+        let broken = dir.path().join("broken.db");
+        std::fs::write(&broken, "not a database").unwrap();
+        assert!(matches!(
+            ds.restore_backup(&broken),
+            Err(Error::Format { .. })
+        ));
+        let foreign = dir.path().join("foreign.db");
+        Connection::open(&foreign)
+            .unwrap()
+            .execute_batch("CREATE TABLE t (x)")
             .unwrap();
-        ds.add_work(1, "alice", "work1-2", 2, &"2025-01-10".parse().unwrap())
+        assert!(matches!(
+            ds.restore_backup(&foreign),
+            Err(Error::Format { .. })
+        ));
+        assert_eq!(ds.list_customer().unwrap().len(), 1);
+        assert!(matches!(
+            in_memory_datastore().backup(None, 1),
+            Err(Error::Unsupported(_))
+        ));
+
+        // Pruning a folder of the user's choice leaves other databases alone.
+        let shared = dir.path().join("shared");
+        std::fs::create_dir(&shared).unwrap();
+        std::fs::write(shared.join("maint-prod.db"), "not a backup").unwrap();
+        std::fs::write(shared.join("maint-20250101-120000.db"), "not a backup").unwrap();
+        ds.backup(Some(&shared), 1).unwrap();
+        let kept = ds.backup(Some(&shared), 1).unwrap();
+        assert_eq!(count(&shared), 3);
+        assert!(kept.exists());
+        assert!(shared.join("maint-prod.db").exists());
+        assert!(shared.join("maint-20250101-120000.db").exists());
+
+        // Opening a database made by an older version backs it up first.
+        let old = dir.path().join("old.db");
+        let conn = Connection::open(&old).unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.pragma_update(None, "user_version", 1).unwrap();
+        drop(conn);
+        DataStore::open(&old).unwrap();
+        let old_backups = dir.path().join("old.db.backups");
+        assert_eq!(count(&old_backups), 1);
+        let backup = std::fs::read_dir(&old_backups).unwrap().next().unwrap();
+        let version: usize = Connection::open(backup.unwrap().path())
+            .unwrap()
+            .pragma_query_value(None, "user_version", |r| r.get(0))
             .unwrap();
-        ds.add_work(2, "alice", "work2", 3, &"2025-12-31".parse().unwrap())
+        assert_eq!(version, 1);
+
+        // So does one from before the migrations were numbered, which has the
+        // tables but no version, and that backup can be restored.
+        let baseline = dir.path().join("baseline.db");
+        let conn = Connection::open(&baseline).unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.execute("INSERT INTO customer (name) VALUES ('old')", [])
             .unwrap();
-        ds.add_work(3, "alice", "work3", 1, &"2026-12-31".parse().unwrap())
+        drop(conn);
+        let ds = DataStore::open(&baseline).unwrap();
+        let baseline_backups = dir.path().join("baseline.db.backups");
+        assert_eq!(count(&baseline_backups), 1);
+        let backup = std::fs::read_dir(&baseline_backups)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let version: usize = Connection::open(&backup)
+            .unwrap()
+            .pragma_query_value(None, "user_version", |r| r.get(0))
             .unwrap();
-        let v = ds.usage(1, "2025-12-31".parse().unwrap()).unwrap();
-        let v = v.cumulative_usage;
-        assert_eq!(v.len(), 3);
-        assert_eq!(v[0].cumulative_points_used, 1);
-        assert_eq!(v[1].cumulative_points_used, 3);
-        assert_eq!(v[2].cumulative_points_used, 6);
+        assert_eq!(version, 0);
+        ds.add_customer("new").unwrap();
+        ds.restore_backup(&backup).unwrap();
+        let ds = DataStore::open(&baseline).unwrap();
+        assert_eq!(ds.list_customer().unwrap().len(), 1);
     }
 }
@@ -1,9 +1,13 @@
 use clap::Parser;
-use std::{env, path::PathBuf, process::ExitCode};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
 use cmd::Cli;
 use edit::edit;
-use maint::DataStore;
+use maint::{DataStore, Journal, Store};
 
 pub mod cmd;
 
@@ -23,12 +27,53 @@ fn db_path() -> PathBuf {
     PathBuf::from(path)
 }
 
+/// The journal `MAINT_JOURNAL` asks for SQLite databases: `rollback`, the
+/// default, or `wal`, which only works while everyone using the file is on one
+/// machine.
+fn journal() -> Result<Journal, maint::Error> {
+    match env::var("MAINT_JOURNAL").as_deref() {
+        Err(_) | Ok("rollback") => Ok(Journal::Rollback),
+        Ok("wal") => Ok(Journal::Wal),
+        Ok(other) => Err(maint::Error::Unsupported(format!(
+            "MAINT_JOURNAL={} (use rollback or wal)",
+            other
+        ))),
+    }
+}
+
+/// Opens the store `MAINT_DB` points at: `dir:<path>` keeps the records as YAML
+/// files under <path>, and `postgres://...` uses a PostgreSQL database.
+fn open(db_path: &Path) -> Result<Box<dyn Store>, maint::Error> {
+    let url = db_path.to_str().unwrap_or_default();
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Box::new(maint::PgStore::connect(url)?));
+        #[cfg(not(feature = "postgres"))]
+        return Err(maint::Error::Unsupported(
+            "PostgreSQL databases in this build (enable the postgres feature)".to_string(),
+        ));
+    }
+    Ok(match url.strip_prefix("dir:") {
+        Some(dir) => Box::new(DataStore::open_dir(dir)?),
+        None => Box::new(DataStore::open_with_journal(db_path, journal()?)?),
+    })
+}
+
 fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
     let db_path = db_path();
-    let ds = DataStore::open(db_path).unwrap();
+    let ds = match open(&db_path) {
+        Ok(ds) => ds,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
 
     let cli = Cli::parse();
-    cli.run(&ds)?;
+    if let Err(e) = cli.run(ds.as_ref()).and_then(|_| Ok(ds.flush()?)) {
+        eprintln!("Error: {}", e);
+        return Ok(ExitCode::FAILURE);
+    }
 
     Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,368 @@
+//! A store that keeps everything in memory, for tests and for embedding.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+
+use crate::{
+    Contract, ContractUsage, CumulativeUsage, Customer, Entity, Error, FieldError, Overlap,
+    Request, Store, Validate, Work,
+};
+
+/// A `Store` whose records live only as long as it does. It validates records
+/// like `DataStore` does, but keeps no history, trash, notes or attachments.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    data: RefCell<Data>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Data {
+    customers: BTreeMap<u32, Customer>,
+    contracts: BTreeMap<u32, Contract>,
+    requests: BTreeMap<u32, Request>,
+    work: BTreeMap<u32, Work>,
+}
+
+/// The id for a new record in `records`.
+fn next_id<T>(records: &BTreeMap<u32, T>) -> u32 {
+    records.last_key_value().map_or(1, |(id, _)| id + 1)
+}
+
+fn check<T: Validate>(entity: &T) -> Result<(), Error> {
+    let errors = entity.validate();
+    if !errors.is_empty() {
+        return Err(Error::Validation(errors));
+    }
+    Ok(())
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails if `contract` overlaps in time with another contract of the same
+    /// customer.
+    fn check_overlap(&self, contract: &Contract) -> Result<(), Error> {
+        let ids: Vec<u32> = self
+            .data
+            .borrow()
+            .contracts
+            .values()
+            .filter(|c| {
+                c.customer_id == contract.customer_id
+                    && c.id != contract.id
+                    && c.start_date <= contract.end_date
+                    && c.end_date >= contract.start_date
+            })
+            .map(|c| c.id)
+            .collect();
+        if !ids.is_empty() {
+            return Err(Error::Overlap(ids));
+        }
+        Ok(())
+    }
+
+    /// Like `check`, but also makes sure the work is not dated before its request.
+    fn check_work(&self, work: &Work) -> Result<(), Error> {
+        let mut errors = work.validate();
+        if let Some(request) = self.data.borrow().requests.get(&work.request_id)
+            && work.work_date < request.request_date
+        {
+            errors.push(FieldError::new(
+                "work_date",
+                &format!(
+                    "must not be before the request date ({})",
+                    request.request_date
+                ),
+            ));
+        }
+        if !errors.is_empty() {
+            return Err(Error::Validation(errors));
+        }
+        Ok(())
+    }
+}
+
+impl Store for MemoryStore {
+    fn add_customer(&self, name: &str) -> Result<usize, Error> {
+        let mut customer = Customer {
+            id: 0,
+            name: name.to_string(),
+        };
+        check(&customer)?;
+        let mut data = self.data.borrow_mut();
+        customer.id = next_id(&data.customers);
+        data.customers.insert(customer.id, customer);
+        Ok(1)
+    }
+
+    fn add_contract(
+        &self,
+        customer_id: u32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        total_points: u32,
+        overlap: Overlap,
+    ) -> Result<usize, Error> {
+        let mut contract = Contract {
+            id: 0,
+            customer_id,
+            start_date: *start_date,
+            end_date: *end_date,
+            total_points,
+        };
+        check(&contract)?;
+        if overlap == Overlap::Reject {
+            self.check_overlap(&contract)?;
+        }
+        let mut data = self.data.borrow_mut();
+        contract.id = next_id(&data.contracts);
+        data.contracts.insert(contract.id, contract);
+        Ok(1)
+    }
+
+    fn add_request(
+        &self,
+        contract_id: u32,
+        description: &str,
+        request_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        let mut request = Request {
+            id: 0,
+            contract_id,
+            description: description.to_string(),
+            request_date: *request_date,
+        };
+        check(&request)?;
+        let mut data = self.data.borrow_mut();
+        request.id = next_id(&data.requests);
+        data.requests.insert(request.id, request);
+        Ok(1)
+    }
+
+    fn add_work(
+        &self,
+        request_id: u32,
+        worker: &str,
+        description: &str,
+        points_used: u32,
+        work_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        let mut work = Work {
+            id: 0,
+            request_id,
+            worker: worker.to_string(),
+            description: description.to_string(),
+            points_used,
+            work_date: *work_date,
+        };
+        self.check_work(&work)?;
+        let mut data = self.data.borrow_mut();
+        work.id = next_id(&data.work);
+        data.work.insert(work.id, work);
+        Ok(1)
+    }
+
+    fn get_customer(&self, id: u32) -> Result<Customer, Error> {
+        let data = self.data.borrow();
+        let customer = data.customers.get(&id);
+        customer
+            .cloned()
+            .ok_or(Error::NotFound(Entity::Customer, id))
+    }
+
+    fn get_contract(&self, id: u32) -> Result<Contract, Error> {
+        let data = self.data.borrow();
+        let contract = data.contracts.get(&id);
+        contract
+            .cloned()
+            .ok_or(Error::NotFound(Entity::Contract, id))
+    }
+
+    fn get_request(&self, id: u32) -> Result<Request, Error> {
+        let data = self.data.borrow();
+        let request = data.requests.get(&id);
+        request.cloned().ok_or(Error::NotFound(Entity::Request, id))
+    }
+
+    fn get_work(&self, id: u32) -> Result<Work, Error> {
+        let data = self.data.borrow();
+        let work = data.work.get(&id);
+        work.cloned().ok_or(Error::NotFound(Entity::Work, id))
+    }
+
+    fn list_customer(&self) -> Result<Vec<Customer>, Error> {
+        Ok(self.data.borrow().customers.values().cloned().collect())
+    }
+
+    fn list_contract(&self) -> Result<Vec<Contract>, Error> {
+        Ok(self.data.borrow().contracts.values().cloned().collect())
+    }
+
+    fn list_request(&self) -> Result<Vec<Request>, Error> {
+        Ok(self.data.borrow().requests.values().cloned().collect())
+    }
+
+    fn list_work(&self) -> Result<Vec<Work>, Error> {
+        Ok(self.data.borrow().work.values().cloned().collect())
+    }
+
+    fn save_customer(&self, entity: Customer) -> Result<usize, Error> {
+        check(&entity)?;
+        let mut data = self.data.borrow_mut();
+        let Some(customer) = data.customers.get_mut(&entity.id) else {
+            return Ok(0);
+        };
+        *customer = entity;
+        Ok(1)
+    }
+
+    fn save_contract(&self, entity: Contract, overlap: Overlap) -> Result<usize, Error> {
+        check(&entity)?;
+        let moved = self
+            .data
+            .borrow()
+            .contracts
+            .get(&entity.id)
+            .is_some_and(|old| entity.moved(old));
+        if overlap == Overlap::Reject && moved {
+            self.check_overlap(&entity)?;
+        }
+        let mut data = self.data.borrow_mut();
+        let Some(contract) = data.contracts.get_mut(&entity.id) else {
+            return Ok(0);
+        };
+        *contract = entity;
+        Ok(1)
+    }
+
+    fn save_request(&self, entity: Request) -> Result<usize, Error> {
+        check(&entity)?;
+        let mut data = self.data.borrow_mut();
+        let Some(request) = data.requests.get_mut(&entity.id) else {
+            return Ok(0);
+        };
+        *request = entity;
+        Ok(1)
+    }
+
+    fn save_work(&self, entity: Work) -> Result<usize, Error> {
+        self.check_work(&entity)?;
+        let mut data = self.data.borrow_mut();
+        let Some(work) = data.work.get_mut(&entity.id) else {
+            return Ok(0);
+        };
+        *work = entity;
+        Ok(1)
+    }
+
+    fn delete(&self, entity: Entity, id: u32) -> Result<usize, Error> {
+        let mut data = self.data.borrow_mut();
+        let (exists, child) = match entity {
+            Entity::Customer => (
+                data.customers.contains_key(&id),
+                data.contracts
+                    .values()
+                    .find(|c| c.customer_id == id)
+                    .map(|c| (Entity::Contract, c.id)),
+            ),
+            Entity::Contract => (
+                data.contracts.contains_key(&id),
+                data.requests
+                    .values()
+                    .find(|r| r.contract_id == id)
+                    .map(|r| (Entity::Request, r.id)),
+            ),
+            Entity::Request => (
+                data.requests.contains_key(&id),
+                data.work
+                    .values()
+                    .find(|w| w.request_id == id)
+                    .map(|w| (Entity::Work, w.id)),
+            ),
+            Entity::Work => (data.work.contains_key(&id), None),
+            _ => return Err(Error::NotTrashable(entity)),
+        };
+        if !exists {
+            return Err(Error::NotFound(entity, id));
+        }
+        if let Some((child, child_id)) = child {
+            return Err(Error::InUse {
+                entity,
+                id,
+                child,
+                child_id,
+            });
+        }
+        match entity {
+            Entity::Customer => {
+                data.customers.remove(&id);
+            }
+            Entity::Contract => {
+                data.contracts.remove(&id);
+            }
+            Entity::Request => {
+                data.requests.remove(&id);
+            }
+            _ => {
+                data.work.remove(&id);
+            }
+        }
+        Ok(1)
+    }
+
+    fn usage(&self, contract_id: u32, date: NaiveDate) -> Result<ContractUsage, Error> {
+        let contract = self.get_contract(contract_id)?;
+        let data = self.data.borrow();
+
+        let mut rows: Vec<(&Request, &Work)> = data
+            .work
+            .values()
+            .filter(|w| w.work_date <= date)
+            .filter_map(|w| Some((data.requests.get(&w.request_id)?, w)))
+            .filter(|(r, _)| {
+                r.contract_id == contract_id
+                    && (contract.start_date..=contract.end_date).contains(&r.request_date)
+            })
+            .collect();
+        rows.sort_by_key(|(r, w)| (r.request_date, w.work_date, w.id));
+
+        let mut cumulative_points_used = 0;
+        let cumulative_usage = rows
+            .into_iter()
+            .map(|(request, work)| {
+                cumulative_points_used += work.points_used;
+                CumulativeUsage {
+                    request_id: request.id,
+                    request_date: request.request_date,
+                    request_description: request.description.clone(),
+                    worker: work.worker.clone(),
+                    work_date: work.work_date,
+                    work_description: work.description.clone(),
+                    points_used: work.points_used,
+                    cumulative_points_used,
+                }
+            })
+            .collect();
+
+        Ok(ContractUsage {
+            contract_id: contract.id,
+            start_date: contract.start_date,
+            end_date: contract.end_date,
+            total_points: contract.total_points,
+            cumulative_usage,
+        })
+    }
+
+    fn batch(&self, dry_run: bool, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        let before = self.data.borrow().clone();
+        let result = f();
+        if result.is_err() || dry_run {
+            *self.data.borrow_mut() = before;
+        }
+        result
+    }
+}
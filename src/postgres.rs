@@ -0,0 +1,1440 @@
+//! A store kept in a PostgreSQL database, so a team can share one dataset.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use postgres::types::ToSql;
+use postgres::{Client, NoTls, Row};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    Attachment, AuditEntry, Contract, ContractUsage, CumulativeUsage, Customer, EXPORT_VERSION,
+    Entity, Error, Export, FieldError, ImportSummary, Overlap, Request, RequestNote, RowError,
+    SearchHit, Store, TrashEntry, Validate, Work, current_user, update_action,
+};
+
+/// The same tables as the SQLite schema. Search needs no table of its own, as
+/// PostgreSQL matches text directly.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE customer (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        name TEXT NOT NULL,
+        deleted_at TIMESTAMPTZ
+    );
+    CREATE TABLE contract (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        customer_id INTEGER NOT NULL REFERENCES customer(id),
+        start_date DATE NOT NULL,
+        end_date DATE NOT NULL,
+        total_points INTEGER NOT NULL,
+        deleted_at TIMESTAMPTZ
+    );
+    CREATE TABLE request (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        contract_id INTEGER NOT NULL REFERENCES contract(id),
+        description TEXT NOT NULL,
+        request_date DATE NOT NULL,
+        deleted_at TIMESTAMPTZ
+    );
+    CREATE TABLE work (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        request_id INTEGER NOT NULL REFERENCES request(id),
+        worker TEXT NOT NULL,
+        description TEXT NOT NULL,
+        points_used INTEGER NOT NULL,
+        work_date DATE NOT NULL,
+        deleted_at TIMESTAMPTZ
+    );
+    ",
+    "
+    CREATE TABLE request_note (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        request_id INTEGER NOT NULL REFERENCES request(id),
+        author TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL,
+        body TEXT NOT NULL
+    );
+    CREATE INDEX request_note_request_id ON request_note (request_id);
+
+    CREATE TABLE attachment_blob (
+        sha256 TEXT PRIMARY KEY,
+        data BYTEA NOT NULL
+    );
+    CREATE TABLE attachment (
+        id INTEGER GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+        request_id INTEGER NOT NULL REFERENCES request(id),
+        work_id INTEGER REFERENCES work(id),
+        filename TEXT NOT NULL,
+        size BIGINT NOT NULL,
+        sha256 TEXT NOT NULL REFERENCES attachment_blob(sha256),
+        created_at TIMESTAMPTZ NOT NULL
+    );
+    CREATE INDEX attachment_request_id ON attachment (request_id);
+
+    CREATE TABLE audit (
+        id INTEGER GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+        entity TEXT NOT NULL,
+        entity_id INTEGER NOT NULL,
+        action TEXT NOT NULL,
+        old_values JSONB,
+        new_values JSONB,
+        \"user\" TEXT NOT NULL,
+        changed_at TIMESTAMPTZ NOT NULL,
+        reverts INTEGER REFERENCES audit(id),
+        operation INTEGER NOT NULL
+    );
+    CREATE INDEX audit_entity ON audit (entity, entity_id);
+    CREATE SEQUENCE audit_operation;
+    CREATE FUNCTION audit_append_only() RETURNS trigger LANGUAGE plpgsql AS $$
+        BEGIN
+            RAISE EXCEPTION 'audit log is append-only';
+        END
+    $$;
+    CREATE TRIGGER audit_append_only BEFORE UPDATE OR DELETE ON audit
+        FOR EACH ROW EXECUTE FUNCTION audit_append_only();
+    ",
+];
+
+/// A `Store` backed by PostgreSQL. Like `DataStore`, it records every change
+/// for history and undo and moves deleted records to the trash; only backups
+/// are not supported.
+pub struct PgStore {
+    client: RefCell<Client>,
+    /// How many `atomic` blocks are open, so only the outermost one starts
+    /// and ends the transaction.
+    depth: Cell<u32>,
+    /// The audit log operation of the open transaction, once it changed
+    /// something.
+    operation: Cell<Option<i32>>,
+}
+
+// Postgres has no unsigned integers, so ids and points are stored as INTEGER.
+fn int(value: u32) -> i32 {
+    value as i32
+}
+
+/// `id`, or `None` for 0, which stands for a new record.
+fn keep(id: u32) -> Option<i32> {
+    (id != 0).then(|| int(id))
+}
+
+fn customer(row: &Row) -> Customer {
+    Customer {
+        id: row.get::<_, i32>("id") as u32,
+        name: row.get("name"),
+    }
+}
+
+fn contract(row: &Row) -> Contract {
+    Contract {
+        id: row.get::<_, i32>("id") as u32,
+        customer_id: row.get::<_, i32>("customer_id") as u32,
+        start_date: row.get("start_date"),
+        end_date: row.get("end_date"),
+        total_points: row.get::<_, i32>("total_points") as u32,
+    }
+}
+
+fn request(row: &Row) -> Request {
+    Request {
+        id: row.get::<_, i32>("id") as u32,
+        contract_id: row.get::<_, i32>("contract_id") as u32,
+        description: row.get("description"),
+        request_date: row.get("request_date"),
+    }
+}
+
+fn work(row: &Row) -> Work {
+    Work {
+        id: row.get::<_, i32>("id") as u32,
+        request_id: row.get::<_, i32>("request_id") as u32,
+        worker: row.get("worker"),
+        description: row.get("description"),
+        points_used: row.get::<_, i32>("points_used") as u32,
+        work_date: row.get("work_date"),
+    }
+}
+
+fn note(row: &Row) -> RequestNote {
+    RequestNote {
+        id: row.get::<_, i32>("id") as u32,
+        request_id: row.get::<_, i32>("request_id") as u32,
+        author: row.get("author"),
+        created_at: row.get("created_at"),
+        body: row.get("body"),
+    }
+}
+
+fn attachment(row: &Row) -> Attachment {
+    Attachment {
+        id: row.get::<_, i32>("id") as u32,
+        request_id: row.get::<_, i32>("request_id") as u32,
+        work_id: row.get::<_, Option<i32>>("work_id").map(|id| id as u32),
+        filename: row.get("filename"),
+        size: row.get::<_, i64>("size") as u64,
+        sha256: row.get("sha256"),
+        created_at: row.get("created_at"),
+    }
+}
+
+fn audit_entry(row: &Row) -> AuditEntry {
+    AuditEntry {
+        id: row.get::<_, i32>("id") as u32,
+        entity: row.get("entity"),
+        entity_id: row.get::<_, i32>("entity_id") as u32,
+        action: row.get("action"),
+        old_values: row.get("old_values"),
+        new_values: row.get("new_values"),
+        user: row.get("user"),
+        changed_at: row.get("changed_at"),
+        reverts: row.get::<_, Option<i32>>("reverts").map(|id| id as u32),
+        operation: Some(row.get::<_, i32>("operation") as u32),
+    }
+}
+
+fn check<T: Validate>(entity: &T) -> Result<(), Error> {
+    let errors = entity.validate();
+    if !errors.is_empty() {
+        return Err(Error::Validation(errors));
+    }
+    Ok(())
+}
+
+impl PgStore {
+    /// Connects to the database at `url` (`postgres://user@host/dbname`) and
+    /// creates or upgrades the schema.
+    pub fn connect(url: &str) -> Result<Self, Error> {
+        let store = PgStore {
+            client: RefCell::new(Client::connect(url, NoTls)?),
+            depth: Cell::new(0),
+            operation: Cell::new(None),
+        };
+        store.init()?;
+
+        Ok(store)
+    }
+
+    fn init(&self) -> Result<(), Error> {
+        self.client
+            .borrow_mut()
+            .batch_execute("CREATE TABLE IF NOT EXISTS maint_schema (version INTEGER NOT NULL)")?;
+        self.atomic(|| {
+            // Keeps concurrent first runs from migrating twice.
+            self.execute("LOCK TABLE maint_schema", &[])?;
+            let version = self
+                .query_opt("SELECT version FROM maint_schema", &[])?
+                .map_or(0, |r| r.get::<_, i32>(0) as usize);
+            if version == MIGRATIONS.len() {
+                return Ok(());
+            }
+            for migration in MIGRATIONS.iter().skip(version) {
+                self.client.borrow_mut().batch_execute(migration)?;
+            }
+            self.execute("DELETE FROM maint_schema", &[])?;
+            self.execute(
+                "INSERT INTO maint_schema (version) VALUES ($1)",
+                &[&(MIGRATIONS.len() as i32)],
+            )?;
+
+            Ok(())
+        })
+    }
+
+    fn query(&self, query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Vec<Row>, Error> {
+        Ok(self.client.borrow_mut().query(query, params)?)
+    }
+
+    fn query_opt(&self, query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Option<Row>, Error> {
+        Ok(self.client.borrow_mut().query_opt(query, params)?)
+    }
+
+    fn execute(&self, query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<u64, Error> {
+        Ok(self.client.borrow_mut().execute(query, params)?)
+    }
+
+    /// Runs `f` inside a transaction, or a savepoint if one is already open,
+    /// rolling back everything it did if it fails or `keep` is false.
+    fn transaction<T>(&self, keep: bool, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        let depth = self.depth.get();
+        let (begin, commit, rollback) = if depth == 0 {
+            (
+                "BEGIN".to_string(),
+                "COMMIT".to_string(),
+                "ROLLBACK".to_string(),
+            )
+        } else {
+            (
+                format!("SAVEPOINT atomic{}", depth),
+                format!("RELEASE atomic{}", depth),
+                format!("ROLLBACK TO atomic{0}; RELEASE atomic{0}", depth),
+            )
+        };
+        self.client.borrow_mut().batch_execute(&begin)?;
+        self.depth.set(depth + 1);
+        let result = f();
+        self.depth.set(depth);
+        if depth == 0 {
+            self.operation.set(None);
+        }
+        match result {
+            Ok(value) if keep => {
+                self.client.borrow_mut().batch_execute(&commit)?;
+                Ok(value)
+            }
+            result => {
+                self.client.borrow_mut().batch_execute(&rollback)?;
+                result
+            }
+        }
+    }
+
+    fn atomic<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        self.transaction(true, f)
+    }
+
+    /// Fails if `contract` overlaps in time with another contract of the same
+    /// customer.
+    fn check_overlap(&self, contract: &Contract) -> Result<(), Error> {
+        let ids: Vec<u32> = self
+            .query(
+                "SELECT id FROM contract
+                    WHERE customer_id = $1
+                    AND id != $2
+                    AND deleted_at IS NULL
+                    AND start_date <= $3
+                    AND end_date >= $4
+                    ORDER BY id",
+                &[
+                    &int(contract.customer_id),
+                    &int(contract.id),
+                    &contract.end_date,
+                    &contract.start_date,
+                ],
+            )?
+            .iter()
+            .map(|r| r.get::<_, i32>(0) as u32)
+            .collect();
+        if !ids.is_empty() {
+            return Err(Error::Overlap(ids));
+        }
+        Ok(())
+    }
+
+    /// Like `check`, but also makes sure the work is not dated before its request.
+    fn check_work(&self, work: &Work) -> Result<(), Error> {
+        let mut errors = work.validate();
+        if let Some(row) = self.query_opt(
+            "SELECT request_date FROM request WHERE id = $1",
+            &[&int(work.request_id)],
+        )? {
+            let request_date: NaiveDate = row.get(0);
+            if work.work_date < request_date {
+                errors.push(FieldError::new(
+                    "work_date",
+                    &format!("must not be before the request date ({})", request_date),
+                ));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Error::Validation(errors));
+        }
+        Ok(())
+    }
+
+    fn get<T>(&self, entity: Entity, id: u32, from_row: fn(&Row) -> T) -> Result<T, Error> {
+        let row = self.query_opt(
+            &format!("SELECT * FROM {} WHERE id = $1", entity.table()),
+            &[&int(id)],
+        )?;
+        row.map(|r| from_row(&r)).ok_or(Error::NotFound(entity, id))
+    }
+
+    fn list<T>(&self, entity: Entity, from_row: fn(&Row) -> T) -> Result<Vec<T>, Error> {
+        let rows = self.query(
+            &format!(
+                "SELECT * FROM {} WHERE deleted_at IS NULL ORDER BY id",
+                entity.table()
+            ),
+            &[],
+        )?;
+        Ok(rows.iter().map(from_row).collect())
+    }
+
+    fn query_one(&self, query: &str, params: &[&(dyn ToSql + Sync)]) -> Result<Row, Error> {
+        Ok(self.client.borrow_mut().query_one(query, params)?)
+    }
+
+    /// The audit log representation of a record, or `None` if it does not
+    /// exist. The same as `DataStore`'s, so histories read alike.
+    fn snapshot(&self, entity: Entity, id: u32) -> Result<Option<serde_json::Value>, Error> {
+        let value = match entity {
+            Entity::Customer => self.get_customer(id).map(serde_json::to_value),
+            Entity::Contract => self.get_contract(id).map(serde_json::to_value),
+            Entity::Request => self.get_request(id).map(serde_json::to_value),
+            Entity::Work => self.get_work(id).map(serde_json::to_value),
+            Entity::Note => self.get(entity, id, note).map(serde_json::to_value),
+            Entity::Attachment => self.get_attachment(id).map(serde_json::to_value),
+        };
+        let mut value = match value {
+            Ok(value) => value.unwrap(),
+            Err(Error::NotFound(..)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if entity.is_trashable()
+            && let Some(deleted_at) = self.deleted_at(entity, id)?
+        {
+            value["deleted_at"] = deleted_at.to_rfc3339().into();
+        }
+
+        Ok(Some(value))
+    }
+
+    /// Appends a row to the audit log for a change from `old` to the current
+    /// state of the record, inside the same `atomic` block as the change.
+    fn audit(
+        &self,
+        entity: Entity,
+        id: u32,
+        action: &str,
+        old: Option<serde_json::Value>,
+    ) -> Result<(), Error> {
+        let new = self.snapshot(entity, id)?;
+        if old.is_none() && new.is_none() {
+            return Ok(());
+        }
+        self.append_audit(entity, id, action, old, new, None)
+    }
+
+    fn append_audit(
+        &self,
+        entity: Entity,
+        id: u32,
+        action: &str,
+        old: Option<serde_json::Value>,
+        new: Option<serde_json::Value>,
+        reverts: Option<u32>,
+    ) -> Result<(), Error> {
+        let operation = match self.operation.get() {
+            Some(operation) => operation,
+            None => {
+                let row = self.query_one("SELECT nextval('audit_operation')::INTEGER", &[])?;
+                let operation = row.get(0);
+                self.operation.set(Some(operation));
+                operation
+            }
+        };
+        self.execute(
+            "INSERT INTO audit
+                (entity, entity_id, action, old_values, new_values, \"user\", changed_at,
+                    reverts, operation)
+                VALUES ($1, $2, $3, $4, $5, $6, now(), $7, $8)",
+            &[
+                &entity.to_string(),
+                &int(id),
+                &action,
+                &old,
+                &new,
+                &current_user(),
+                &reverts.map(int),
+                &operation,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn revert(&self, entry: &AuditEntry) -> Result<(), Error> {
+        let entity: Entity = entry.entity.parse().map_err(Error::Undo)?;
+        let table = entity.table();
+        let current = self.snapshot(entity, entry.entity_id)?;
+        if current != entry.new_values {
+            return Err(Error::Undo(format!(
+                "{} {} has changed since change {}",
+                entity, entry.entity_id, entry.id
+            )));
+        }
+        // The columns a snapshot has, quoted, leaving out the id.
+        let columns = |values: &[&serde_json::Value]| {
+            let mut columns: Vec<String> = values
+                .iter()
+                .filter_map(|v| v.as_object())
+                .flat_map(|object| object.keys())
+                .filter(|column| *column != "id")
+                .map(|column| format!("\"{}\"", column.replace('"', "\"\"")))
+                .collect();
+            columns.sort();
+            columns.dedup();
+            columns.join(", ")
+        };
+
+        let action = match (&entry.old_values, &entry.new_values) {
+            (None, Some(_)) => {
+                self.execute(
+                    &format!("DELETE FROM {} WHERE id = $1", table),
+                    &[&int(entry.entity_id)],
+                )?;
+                "delete"
+            }
+            (Some(old), Some(new)) => {
+                // Fields the old snapshot lacks, like deleted_at, were NULL.
+                let columns = columns(&[old, new]);
+                self.execute(
+                    &format!(
+                        "UPDATE {0} SET ({1}) = (
+                            SELECT {1} FROM jsonb_populate_record(NULL::{0}, $2)
+                        ) WHERE id = $1",
+                        table, columns
+                    ),
+                    &[&int(entry.entity_id), old],
+                )?;
+                update_action(new, old)
+            }
+            (Some(old), None) => {
+                let columns = columns(&[old]);
+                self.execute(
+                    &format!(
+                        "INSERT INTO {0} (id, {1})
+                            SELECT $1, {1} FROM jsonb_populate_record(NULL::{0}, $2)",
+                        table, columns
+                    ),
+                    &[&int(entry.entity_id), old],
+                )?;
+                "insert"
+            }
+            (None, None) => return Ok(()),
+        };
+
+        let restored = self.snapshot(entity, entry.entity_id)?;
+        self.append_audit(
+            entity,
+            entry.entity_id,
+            action,
+            current,
+            restored,
+            Some(entry.id),
+        )
+    }
+
+    /// Deletes a row for good, recording its last state in the audit log.
+    fn remove(&self, entity: Entity, id: u32) -> Result<(), Error> {
+        let old = self.snapshot(entity, id)?;
+        self.execute(
+            &format!("DELETE FROM {} WHERE id = $1", entity.table()),
+            &[&int(id)],
+        )?;
+        self.audit(entity, id, "delete", old)
+    }
+
+    /// Removes the notes and attachments of a request or work entry.
+    fn purge_dependents(&self, entity: Entity, id: u32) -> Result<(), Error> {
+        let column = match entity {
+            Entity::Request => "request_id",
+            Entity::Work => "work_id",
+            _ => return Ok(()),
+        };
+        let mut dependents = Vec::new();
+        if entity == Entity::Request {
+            for row in self.query(
+                "SELECT id FROM request_note WHERE request_id = $1",
+                &[&int(id)],
+            )? {
+                dependents.push((Entity::Note, row.get::<_, i32>(0) as u32));
+            }
+        }
+        for row in self.query(
+            &format!("SELECT id FROM attachment WHERE {} = $1", column),
+            &[&int(id)],
+        )? {
+            dependents.push((Entity::Attachment, row.get::<_, i32>(0) as u32));
+        }
+        for (entity, id) in dependents {
+            self.remove(entity, id)?;
+        }
+        Ok(())
+    }
+
+    /// The table and column linking records of `entity` to their parent.
+    fn parent_column(entity: Entity) -> Option<(Entity, &'static str)> {
+        match entity {
+            Entity::Contract => Some((Entity::Customer, "customer_id")),
+            Entity::Request => Some((Entity::Contract, "contract_id")),
+            Entity::Work => Some((Entity::Request, "request_id")),
+            _ => None,
+        }
+    }
+
+    /// Some child of a record that is not in the trash, if there is one.
+    fn live_child(&self, entity: Entity, id: u32) -> Result<Option<(Entity, u32)>, Error> {
+        self.first_child(entity, id, "AND deleted_at IS NULL")
+    }
+
+    /// Some child of a record, in the trash or not, if there is one.
+    fn child(&self, entity: Entity, id: u32) -> Result<Option<(Entity, u32)>, Error> {
+        self.first_child(entity, id, "")
+    }
+
+    fn first_child(
+        &self,
+        entity: Entity,
+        id: u32,
+        condition: &str,
+    ) -> Result<Option<(Entity, u32)>, Error> {
+        let child = match entity {
+            Entity::Customer => Entity::Contract,
+            Entity::Contract => Entity::Request,
+            Entity::Request => Entity::Work,
+            _ => return Ok(None),
+        };
+        let (_, column) = Self::parent_column(child).unwrap();
+        let row = self.query_opt(
+            &format!(
+                "SELECT min(id) FROM {} WHERE {} = $1 {}",
+                child.table(),
+                column,
+                condition
+            ),
+            &[&int(id)],
+        )?;
+        Ok(row
+            .and_then(|r| r.get::<_, Option<i32>>(0))
+            .map(|child_id| (child, child_id as u32)))
+    }
+
+    // The insert_* functions save a record as it is, without checking it, and
+    // belong inside `atomic` with the checks, like `DataStore`'s. They keep the
+    // record's id unless it is 0, and return the id it was saved under.
+
+    /// SQL for the id of a record of `entity`: `$1`, or the next one from the
+    /// table's sequence if that is NULL.
+    fn new_id(entity: Entity) -> String {
+        format!(
+            "COALESCE($1, nextval(pg_get_serial_sequence('{}', 'id'))::INTEGER)",
+            entity.table()
+        )
+    }
+
+    fn insert_customer(&self, customer: &Customer) -> Result<u32, Error> {
+        let row = self.query_one(
+            &format!(
+                "INSERT INTO customer (id, name) VALUES ({}, $2) RETURNING id",
+                Self::new_id(Entity::Customer)
+            ),
+            &[&keep(customer.id), &customer.name],
+        )?;
+        let id = row.get::<_, i32>(0) as u32;
+        self.audit(Entity::Customer, id, "insert", None)?;
+        Ok(id)
+    }
+
+    fn insert_contract(&self, contract: &Contract) -> Result<u32, Error> {
+        let row = self.query_one(
+            &format!(
+                "INSERT INTO contract (id, customer_id, start_date, end_date, total_points)
+                    VALUES ({}, $2, $3, $4, $5) RETURNING id",
+                Self::new_id(Entity::Contract)
+            ),
+            &[
+                &keep(contract.id),
+                &int(contract.customer_id),
+                &contract.start_date,
+                &contract.end_date,
+                &int(contract.total_points),
+            ],
+        )?;
+        let id = row.get::<_, i32>(0) as u32;
+        self.audit(Entity::Contract, id, "insert", None)?;
+        Ok(id)
+    }
+
+    fn insert_request(&self, request: &Request) -> Result<u32, Error> {
+        let row = self.query_one(
+            &format!(
+                "INSERT INTO request (id, contract_id, description, request_date)
+                    VALUES ({}, $2, $3, $4) RETURNING id",
+                Self::new_id(Entity::Request)
+            ),
+            &[
+                &keep(request.id),
+                &int(request.contract_id),
+                &request.description,
+                &request.request_date,
+            ],
+        )?;
+        let id = row.get::<_, i32>(0) as u32;
+        self.audit(Entity::Request, id, "insert", None)?;
+        Ok(id)
+    }
+
+    fn insert_work(&self, work: &Work) -> Result<u32, Error> {
+        let row = self.query_one(
+            &format!(
+                "INSERT INTO work (id, request_id, worker, description, points_used, work_date)
+                    VALUES ({}, $2, $3, $4, $5, $6) RETURNING id",
+                Self::new_id(Entity::Work)
+            ),
+            &[
+                &keep(work.id),
+                &int(work.request_id),
+                &work.worker,
+                &work.description,
+                &int(work.points_used),
+                &work.work_date,
+            ],
+        )?;
+        let id = row.get::<_, i32>(0) as u32;
+        self.audit(Entity::Work, id, "insert", None)?;
+        Ok(id)
+    }
+
+    /// The record's `deleted_at`, failing if there is no such record.
+    fn deleted_at(&self, entity: Entity, id: u32) -> Result<Option<DateTime<Utc>>, Error> {
+        let row = self.query_opt(
+            &format!("SELECT deleted_at FROM {} WHERE id = $1", entity.table()),
+            &[&int(id)],
+        )?;
+        row.map(|r| r.get(0)).ok_or(Error::NotFound(entity, id))
+    }
+}
+
+impl Store for PgStore {
+    fn add_customer(&self, name: &str) -> Result<usize, Error> {
+        let customer = Customer {
+            id: 0,
+            name: name.to_string(),
+        };
+        self.atomic(|| {
+            check(&customer)?;
+            self.insert_customer(&customer)
+        })?;
+        Ok(1)
+    }
+
+    fn add_contract(
+        &self,
+        customer_id: u32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        total_points: u32,
+        overlap: Overlap,
+    ) -> Result<usize, Error> {
+        let contract = Contract {
+            id: 0,
+            customer_id,
+            start_date: *start_date,
+            end_date: *end_date,
+            total_points,
+        };
+        self.atomic(|| {
+            check(&contract)?;
+            if overlap == Overlap::Reject {
+                self.check_overlap(&contract)?;
+            }
+            self.insert_contract(&contract)
+        })?;
+        Ok(1)
+    }
+
+    fn add_request(
+        &self,
+        contract_id: u32,
+        description: &str,
+        request_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        let request = Request {
+            id: 0,
+            contract_id,
+            description: description.to_string(),
+            request_date: *request_date,
+        };
+        self.atomic(|| {
+            check(&request)?;
+            self.insert_request(&request)
+        })?;
+        Ok(1)
+    }
+
+    fn add_work(
+        &self,
+        request_id: u32,
+        worker: &str,
+        description: &str,
+        points_used: u32,
+        work_date: &NaiveDate,
+    ) -> Result<usize, Error> {
+        let work = Work {
+            id: 0,
+            request_id,
+            worker: worker.to_string(),
+            description: description.to_string(),
+            points_used,
+            work_date: *work_date,
+        };
+        self.atomic(|| {
+            self.check_work(&work)?;
+            self.insert_work(&work)
+        })?;
+        Ok(1)
+    }
+
+    fn get_customer(&self, id: u32) -> Result<Customer, Error> {
+        self.get(Entity::Customer, id, customer)
+    }
+
+    fn get_contract(&self, id: u32) -> Result<Contract, Error> {
+        self.get(Entity::Contract, id, contract)
+    }
+
+    fn get_request(&self, id: u32) -> Result<Request, Error> {
+        self.get(Entity::Request, id, request)
+    }
+
+    fn get_work(&self, id: u32) -> Result<Work, Error> {
+        self.get(Entity::Work, id, work)
+    }
+
+    fn list_customer(&self) -> Result<Vec<Customer>, Error> {
+        self.list(Entity::Customer, customer)
+    }
+
+    fn list_contract(&self) -> Result<Vec<Contract>, Error> {
+        self.list(Entity::Contract, contract)
+    }
+
+    fn list_request(&self) -> Result<Vec<Request>, Error> {
+        self.list(Entity::Request, request)
+    }
+
+    fn list_work(&self) -> Result<Vec<Work>, Error> {
+        self.list(Entity::Work, work)
+    }
+
+    fn save_customer(&self, entity: Customer) -> Result<usize, Error> {
+        self.atomic(|| {
+            check(&entity)?;
+            let old = self.snapshot(Entity::Customer, entity.id)?;
+            let rows = self.execute(
+                "UPDATE customer SET name = $1 WHERE id = $2",
+                &[&entity.name, &int(entity.id)],
+            )?;
+            self.audit(Entity::Customer, entity.id, "update", old)?;
+            Ok(rows as usize)
+        })
+    }
+
+    fn save_contract(&self, entity: Contract, overlap: Overlap) -> Result<usize, Error> {
+        self.atomic(|| {
+            check(&entity)?;
+            let moved = match self.get_contract(entity.id) {
+                Ok(old) => entity.moved(&old),
+                Err(Error::NotFound(..)) => false,
+                Err(e) => return Err(e),
+            };
+            if overlap == Overlap::Reject && moved {
+                self.check_overlap(&entity)?;
+            }
+            let old = self.snapshot(Entity::Contract, entity.id)?;
+            let rows = self.execute(
+                "UPDATE contract SET
+                    customer_id = $1,
+                    start_date = $2,
+                    end_date = $3,
+                    total_points = $4
+                WHERE
+                    id = $5",
+                &[
+                    &int(entity.customer_id),
+                    &entity.start_date,
+                    &entity.end_date,
+                    &int(entity.total_points),
+                    &int(entity.id),
+                ],
+            )?;
+            self.audit(Entity::Contract, entity.id, "update", old)?;
+            Ok(rows as usize)
+        })
+    }
+
+    fn save_request(&self, entity: Request) -> Result<usize, Error> {
+        self.atomic(|| {
+            check(&entity)?;
+            let old = self.snapshot(Entity::Request, entity.id)?;
+            let rows = self.execute(
+                "UPDATE request SET contract_id = $1, description = $2, request_date = $3
+                    WHERE id = $4",
+                &[
+                    &int(entity.contract_id),
+                    &entity.description,
+                    &entity.request_date,
+                    &int(entity.id),
+                ],
+            )?;
+            self.audit(Entity::Request, entity.id, "update", old)?;
+            Ok(rows as usize)
+        })
+    }
+
+    fn save_work(&self, entity: Work) -> Result<usize, Error> {
+        self.atomic(|| {
+            self.check_work(&entity)?;
+            let old = self.snapshot(Entity::Work, entity.id)?;
+            let rows = self.execute(
+                "UPDATE work SET
+                    request_id = $1,
+                    worker = $2,
+                    description = $3,
+                    points_used = $4,
+                    work_date = $5
+                WHERE
+                    id = $6",
+                &[
+                    &int(entity.request_id),
+                    &entity.worker,
+                    &entity.description,
+                    &int(entity.points_used),
+                    &entity.work_date,
+                    &int(entity.id),
+                ],
+            )?;
+            self.audit(Entity::Work, entity.id, "update", old)?;
+            Ok(rows as usize)
+        })
+    }
+
+    fn delete(&self, entity: Entity, id: u32) -> Result<usize, Error> {
+        if !entity.is_trashable() {
+            return Err(Error::NotTrashable(entity));
+        }
+        self.atomic(|| {
+            self.deleted_at(entity, id)?;
+            let old = self.snapshot(entity, id)?;
+            if let Some((child, child_id)) = self.live_child(entity, id)? {
+                return Err(Error::InUse {
+                    entity,
+                    id,
+                    child,
+                    child_id,
+                });
+            }
+            let rows = self.execute(
+                &format!(
+                    "UPDATE {} SET deleted_at = now() WHERE id = $1 AND deleted_at IS NULL",
+                    entity.table()
+                ),
+                &[&int(id)],
+            )?;
+            if rows > 0 {
+                self.audit(entity, id, "trash", old)?;
+            }
+            Ok(rows as usize)
+        })
+    }
+
+    fn restore(&self, entity: Entity, id: u32) -> Result<usize, Error> {
+        if !entity.is_trashable() {
+            return Err(Error::NotTrashable(entity));
+        }
+        self.atomic(|| {
+            self.deleted_at(entity, id)?;
+            let old = self.snapshot(entity, id)?;
+            if let Some((parent, column)) = Self::parent_column(entity) {
+                let row = self.query_opt(
+                    &format!("SELECT {} FROM {} WHERE id = $1", column, entity.table()),
+                    &[&int(id)],
+                )?;
+                let parent_id = row.map_or(0, |r| r.get::<_, i32>(0) as u32);
+                if self.deleted_at(parent, parent_id)?.is_some() {
+                    return Err(Error::ParentDeleted {
+                        entity,
+                        id,
+                        parent,
+                        parent_id,
+                    });
+                }
+            }
+            let rows = self.execute(
+                &format!(
+                    "UPDATE {} SET deleted_at = NULL WHERE id = $1 AND deleted_at IS NOT NULL",
+                    entity.table()
+                ),
+                &[&int(id)],
+            )?;
+            if rows > 0 {
+                self.audit(entity, id, "restore", old)?;
+            }
+            Ok(rows as usize)
+        })
+    }
+
+    /// Permanently removes records trashed before `deleted_before`, like
+    /// `DataStore::purge`.
+    fn purge(&self, deleted_before: DateTime<Utc>) -> Result<Vec<(Entity, u32)>, Error> {
+        self.atomic(|| {
+            let mut purged = Vec::new();
+            for entity in [
+                Entity::Work,
+                Entity::Request,
+                Entity::Contract,
+                Entity::Customer,
+            ] {
+                let rows = self.query(
+                    &format!(
+                        "SELECT id FROM {} WHERE deleted_at < $1 ORDER BY id",
+                        entity.table()
+                    ),
+                    &[&deleted_before],
+                )?;
+                for row in rows {
+                    let id = row.get::<_, i32>(0) as u32;
+                    if self.child(entity, id)?.is_some() {
+                        continue;
+                    }
+                    self.purge_dependents(entity, id)?;
+                    self.remove(entity, id)?;
+                    purged.push((entity, id));
+                }
+            }
+            // Contents the change history still refers to stay, so that undoing
+            // the removal of an attachment brings its data back too.
+            self.execute(
+                "DELETE FROM attachment_blob
+                    WHERE sha256 NOT IN (SELECT sha256 FROM attachment)
+                    AND sha256 NOT IN (
+                        SELECT old_values ->> 'sha256' FROM audit
+                        WHERE entity = 'attachment' AND old_values IS NOT NULL
+                    )",
+                &[],
+            )?;
+            Ok(purged)
+        })
+    }
+
+    fn add_note(&self, request_id: u32, author: &str, body: &str) -> Result<usize, Error> {
+        self.atomic(|| {
+            let row = self.query_one(
+                "INSERT INTO request_note (request_id, author, created_at, body)
+                    VALUES ($1, $2, now(), $3) RETURNING id",
+                &[&int(request_id), &author, &body],
+            )?;
+            self.audit(Entity::Note, row.get::<_, i32>(0) as u32, "insert", None)?;
+            Ok(1)
+        })
+    }
+
+    fn list_notes(&self, request_id: u32) -> Result<Vec<RequestNote>, Error> {
+        let rows = self.query(
+            "SELECT * FROM request_note WHERE request_id = $1 ORDER BY created_at, id",
+            &[&int(request_id)],
+        )?;
+        Ok(rows.iter().map(note).collect())
+    }
+
+    fn add_attachment(
+        &self,
+        request_id: u32,
+        work_id: Option<u32>,
+        filename: &str,
+        data: &[u8],
+    ) -> Result<usize, Error> {
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        self.atomic(|| {
+            self.execute(
+                "INSERT INTO attachment_blob (sha256, data) VALUES ($1, $2)
+                    ON CONFLICT (sha256) DO NOTHING",
+                &[&sha256, &data],
+            )?;
+            let row = self.query_one(
+                "INSERT INTO attachment (request_id, work_id, filename, size, sha256, created_at)
+                    VALUES ($1, $2, $3, $4, $5, now()) RETURNING id",
+                &[
+                    &int(request_id),
+                    &work_id.map(int),
+                    &filename,
+                    &(data.len() as i64),
+                    &sha256,
+                ],
+            )?;
+            self.audit(
+                Entity::Attachment,
+                row.get::<_, i32>(0) as u32,
+                "insert",
+                None,
+            )?;
+            Ok(1)
+        })
+    }
+
+    fn list_attachments(&self, request_id: u32) -> Result<Vec<Attachment>, Error> {
+        let rows = self.query(
+            "SELECT * FROM attachment WHERE request_id = $1 ORDER BY id",
+            &[&int(request_id)],
+        )?;
+        Ok(rows.iter().map(attachment).collect())
+    }
+
+    fn get_attachment(&self, id: u32) -> Result<Attachment, Error> {
+        self.get(Entity::Attachment, id, attachment)
+    }
+
+    fn attachment_data(&self, id: u32) -> Result<Vec<u8>, Error> {
+        let row = self.query_opt(
+            "SELECT attachment_blob.data FROM attachment
+                INNER JOIN attachment_blob ON attachment.sha256 = attachment_blob.sha256
+                WHERE attachment.id = $1",
+            &[&int(id)],
+        )?;
+        row.map(|r| r.get(0))
+            .ok_or(Error::NotFound(Entity::Attachment, id))
+    }
+
+    fn history(&self, entity: Entity, id: u32) -> Result<Vec<AuditEntry>, Error> {
+        let rows = self.query(
+            "SELECT * FROM audit WHERE entity = $1 AND entity_id = $2 ORDER BY id",
+            &[&entity.to_string(), &int(id)],
+        )?;
+        Ok(rows.iter().map(audit_entry).collect())
+    }
+
+    /// Reverts the `steps` most recent operations that have not been undone
+    /// yet, like `DataStore::undo`.
+    fn undo(&self, steps: u32) -> Result<Vec<AuditEntry>, Error> {
+        self.atomic(|| {
+            let rows = self.query(
+                "SELECT * FROM audit WHERE reverts IS NULL
+                    AND id NOT IN (SELECT reverts FROM audit WHERE reverts IS NOT NULL)
+                    ORDER BY id DESC",
+                &[],
+            )?;
+            let mut entries = Vec::new();
+            let mut operations = Vec::new();
+            for entry in rows.iter().map(audit_entry) {
+                if operations.last() != Some(&entry.operation) {
+                    if operations.len() == steps as usize {
+                        break;
+                    }
+                    operations.push(entry.operation);
+                }
+                entries.push(entry);
+            }
+
+            for entry in &entries {
+                self.revert(entry)?;
+            }
+            Ok(entries)
+        })
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashEntry>, Error> {
+        let rows = self.query(
+            "
+            SELECT 'customer', id, name, deleted_at FROM customer
+                WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'contract', id, start_date || ' - ' || end_date, deleted_at FROM contract
+                WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'request', id, description, deleted_at FROM request
+                WHERE deleted_at IS NOT NULL
+            UNION ALL
+            SELECT 'work', id, description, deleted_at FROM work
+                WHERE deleted_at IS NOT NULL
+            ORDER BY 4 DESC",
+            &[],
+        )?;
+        Ok(rows
+            .iter()
+            .map(|r| TrashEntry {
+                entity: r.get(0),
+                id: r.get::<_, i32>(1) as u32,
+                summary: r.get(2),
+                deleted_at: r.get(3),
+            })
+            .collect())
+    }
+
+    /// Full-text search like `DataStore::search`, with PostgreSQL's English
+    /// text search configuration.
+    fn search(&self, query: &str, limit: u32) -> Result<Vec<SearchHit>, Error> {
+        if query.split_whitespace().next().is_none() {
+            return Ok(Vec::new());
+        }
+        let rows = self.query(
+            "
+            SELECT
+                hit.entity,
+                hit.id,
+                hit.customer_id,
+                hit.customer,
+                ts_headline(
+                    'english', hit.body, query,
+                    'StartSel=[, StopSel=], MaxWords=12, MinWords=4'
+                )
+            FROM
+                (
+                    SELECT 'customer' AS entity, id, id AS customer_id, name AS customer,
+                        name AS body
+                    FROM customer
+                    WHERE deleted_at IS NULL
+                UNION ALL
+                    SELECT 'request', request.id, customer.id, customer.name,
+                        request.description
+                    FROM request
+                        LEFT JOIN contract ON contract.id = request.contract_id
+                        LEFT JOIN customer ON customer.id = contract.customer_id
+                    WHERE request.deleted_at IS NULL
+                UNION ALL
+                    SELECT 'work', work.id, customer.id, customer.name, work.description
+                    FROM work
+                        LEFT JOIN request ON request.id = work.request_id
+                        LEFT JOIN contract ON contract.id = request.contract_id
+                        LEFT JOIN customer ON customer.id = contract.customer_id
+                    WHERE work.deleted_at IS NULL
+                ) AS hit,
+                plainto_tsquery('english', $1) AS query
+            WHERE
+                to_tsvector('english', hit.body) @@ query
+            ORDER BY
+                ts_rank(to_tsvector('english', hit.body), query) DESC, hit.entity, hit.id
+            LIMIT $2
+            ",
+            &[&query, &i64::from(limit)],
+        )?;
+        Ok(rows
+            .iter()
+            .map(|r| SearchHit {
+                entity: r.get(0),
+                id: r.get::<_, i32>(1) as u32,
+                customer_id: r.get::<_, Option<i32>>(2).map(|id| id as u32),
+                customer: r.get(3),
+                snippet: r.get(4),
+            })
+            .collect())
+    }
+
+    /// Saves the records of an `export` like `DataStore::import`. Every record
+    /// gets a savepoint of its own, as a failed statement would otherwise end
+    /// the whole transaction.
+    fn import(
+        &self,
+        export: &Export,
+        merge: bool,
+        overlap: Overlap,
+    ) -> Result<ImportSummary, Error> {
+        if export.version > EXPORT_VERSION {
+            return Err(Error::Unsupported(format!(
+                "export version {} (this build reads up to {})",
+                export.version, EXPORT_VERSION
+            )));
+        }
+
+        // Ids in the export mapped to the ids the records were saved under.
+        let mut customers = HashMap::new();
+        let mut contracts = HashMap::new();
+        let mut requests = HashMap::new();
+        let mut work = 0;
+        let mut errors = Vec::new();
+        let keep = |id: u32| if merge { 0 } else { id };
+        let check = |check: &dyn Fn() -> Result<(), Error>| if merge { check() } else { Ok(()) };
+        let parent = |ids: &HashMap<u32, u32>, entity: Entity, id: u32| {
+            ids.get(&id).copied().ok_or_else(|| {
+                Error::Validation(vec![FieldError::new(
+                    &format!("{}_id", entity),
+                    &format!("{} {} is not in the export or failed to import", entity, id),
+                )])
+            })
+        };
+        let failure =
+            |section: &'static str, index: usize, entity: Entity, id: u32, e: Error| RowError {
+                section: Some(section),
+                row: index as u64 + 1,
+                message: format!("{} {}: {}", entity, id, e).replace("\n  ", " "),
+            };
+
+        self.atomic(|| {
+            if !merge {
+                for table in ["customer", "contract", "request", "work"] {
+                    let row = self.query_one(&format!("SELECT count(*) FROM {}", table), &[])?;
+                    if row.get::<_, i64>(0) > 0 {
+                        return Err(Error::NotEmpty);
+                    }
+                }
+            }
+
+            for (i, c) in export.customers.iter().enumerate() {
+                let customer = Customer {
+                    id: keep(c.id),
+                    name: c.name.clone(),
+                };
+                let result = self.atomic(|| {
+                    check(&|| self::check(&customer))?;
+                    self.insert_customer(&customer)
+                });
+                match result {
+                    Ok(id) => {
+                        customers.insert(c.id, id);
+                    }
+                    Err(e) => errors.push(failure("customers", i, Entity::Customer, c.id, e)),
+                }
+            }
+            for (i, c) in export.contracts.iter().enumerate() {
+                let result =
+                    parent(&customers, Entity::Customer, c.customer_id).and_then(|customer_id| {
+                        let contract = Contract {
+                            id: keep(c.id),
+                            customer_id,
+                            start_date: c.start_date,
+                            end_date: c.end_date,
+                            total_points: c.total_points,
+                        };
+                        self.atomic(|| {
+                            check(&|| {
+                                self::check(&contract)?;
+                                if overlap == Overlap::Reject {
+                                    self.check_overlap(&contract)?;
+                                }
+                                Ok(())
+                            })?;
+                            self.insert_contract(&contract)
+                        })
+                    });
+                match result {
+                    Ok(id) => {
+                        contracts.insert(c.id, id);
+                    }
+                    Err(e) => errors.push(failure("contracts", i, Entity::Contract, c.id, e)),
+                }
+            }
+            for (i, r) in export.requests.iter().enumerate() {
+                let result =
+                    parent(&contracts, Entity::Contract, r.contract_id).and_then(|contract_id| {
+                        let request = Request {
+                            id: keep(r.id),
+                            contract_id,
+                            description: r.description.clone(),
+                            request_date: r.request_date,
+                        };
+                        self.atomic(|| {
+                            check(&|| self::check(&request))?;
+                            self.insert_request(&request)
+                        })
+                    });
+                match result {
+                    Ok(id) => {
+                        requests.insert(r.id, id);
+                    }
+                    Err(e) => errors.push(failure("requests", i, Entity::Request, r.id, e)),
+                }
+            }
+            for (i, w) in export.work.iter().enumerate() {
+                let result =
+                    parent(&requests, Entity::Request, w.request_id).and_then(|request_id| {
+                        let work = Work {
+                            id: keep(w.id),
+                            request_id,
+                            worker: w.worker.clone(),
+                            description: w.description.clone(),
+                            points_used: w.points_used,
+                            work_date: w.work_date,
+                        };
+                        self.atomic(|| {
+                            check(&|| self.check_work(&work))?;
+                            self.insert_work(&work)
+                        })
+                    });
+                match result {
+                    Ok(_) => work += 1,
+                    Err(e) => errors.push(failure("work", i, Entity::Work, w.id, e)),
+                }
+            }
+
+            if !errors.is_empty() {
+                return Err(Error::Rows(errors));
+            }
+            if !merge {
+                // Records saved with their own ids leave the sequences behind.
+                for entity in [
+                    Entity::Customer,
+                    Entity::Contract,
+                    Entity::Request,
+                    Entity::Work,
+                ] {
+                    self.query(
+                        &format!(
+                            "SELECT setval(pg_get_serial_sequence('{0}', 'id'), max(id))
+                                FROM {0} HAVING count(*) > 0",
+                            entity.table()
+                        ),
+                        &[],
+                    )?;
+                }
+            }
+            Ok(ImportSummary {
+                customers: customers.len(),
+                contracts: contracts.len(),
+                requests: requests.len(),
+                work,
+            })
+        })
+    }
+
+    fn usage(&self, contract_id: u32, date: NaiveDate) -> Result<ContractUsage, Error> {
+        let contract = self.get_contract(contract_id)?;
+
+        let rows = self.query(
+            "
+            SELECT
+                request.request_date,
+                request.description,
+                work.worker,
+                work.work_date,
+                work.description,
+                work.points_used,
+                SUM(work.points_used)
+                    OVER (
+                        PARTITION BY contract.id
+                        ORDER BY request.request_date, work.work_date, work.id
+                        ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+                    ) AS cumulative_points_used,
+                request.id
+            FROM
+                contract
+                INNER JOIN request ON contract.id = request.contract_id
+                INNER JOIN work ON request.id = work.request_id
+            WHERE
+                contract.id = $1
+                AND request.request_date BETWEEN contract.start_date and contract.end_date
+                AND work.work_date <= $2
+                AND request.deleted_at IS NULL
+                AND work.deleted_at IS NULL
+            ORDER BY
+                request.request_date, work.work_date, work.id
+            ",
+            &[&int(contract_id), &date],
+        )?;
+        let cumulative_usage = rows
+            .iter()
+            .map(|r| CumulativeUsage {
+                request_date: r.get(0),
+                request_description: r.get(1),
+                worker: r.get(2),
+                work_date: r.get(3),
+                work_description: r.get(4),
+                points_used: r.get::<_, i32>(5) as u32,
+                cumulative_points_used: r.get::<_, i64>(6) as u32,
+                request_id: r.get::<_, i32>(7) as u32,
+            })
+            .collect();
+
+        Ok(ContractUsage {
+            contract_id: contract.id,
+            start_date: contract.start_date,
+            end_date: contract.end_date,
+            total_points: contract.total_points,
+            cumulative_usage,
+        })
+    }
+
+    fn batch(&self, dry_run: bool, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error> {
+        self.transaction(!dry_run, f)
+    }
+}
@@ -0,0 +1,493 @@
+//! The operations the CLI needs from wherever records are kept.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::{
+    Attachment, AuditEntry, BurnStatus, Contract, ContractOverlap, ContractReport, ContractSummary,
+    ContractUsage, Customer, CustomerUsage, DashboardEntry, EXPORT_VERSION, Entity, Error, Export,
+    ExportedContract, ExportedCustomer, ExportedRequest, ExportedWork, FieldError, ImportSummary,
+    Overlap, Request, RequestNote, RequestReport, RequestStatus, SearchHit, TrashEntry,
+    UnaccountedWork, UsageStatement, Work,
+};
+
+/// A place to keep customers, contracts, requests and work.
+///
+/// Stores implement the record operations and `usage`; the reports built on
+/// them come for free. Notes, attachments, history, the trash, search, import
+/// and backups are optional and fail with `Error::Unsupported` unless a store
+/// provides them.
+pub trait Store {
+    fn add_customer(&self, name: &str) -> Result<usize, Error>;
+
+    fn add_contract(
+        &self,
+        customer_id: u32,
+        start_date: &NaiveDate,
+        end_date: &NaiveDate,
+        total_points: u32,
+        overlap: Overlap,
+    ) -> Result<usize, Error>;
+
+    fn add_request(
+        &self,
+        contract_id: u32,
+        description: &str,
+        request_date: &NaiveDate,
+    ) -> Result<usize, Error>;
+
+    fn add_work(
+        &self,
+        request_id: u32,
+        worker: &str,
+        description: &str,
+        points_used: u32,
+        work_date: &NaiveDate,
+    ) -> Result<usize, Error>;
+
+    fn get_customer(&self, id: u32) -> Result<Customer, Error>;
+    fn get_contract(&self, id: u32) -> Result<Contract, Error>;
+    fn get_request(&self, id: u32) -> Result<Request, Error>;
+    fn get_work(&self, id: u32) -> Result<Work, Error>;
+
+    /// Records not in the trash, in id order.
+    fn list_customer(&self) -> Result<Vec<Customer>, Error>;
+    fn list_contract(&self) -> Result<Vec<Contract>, Error>;
+    fn list_request(&self) -> Result<Vec<Request>, Error>;
+    fn list_work(&self) -> Result<Vec<Work>, Error>;
+
+    fn save_customer(&self, entity: Customer) -> Result<usize, Error>;
+    fn save_contract(&self, entity: Contract, overlap: Overlap) -> Result<usize, Error>;
+    fn save_request(&self, entity: Request) -> Result<usize, Error>;
+    fn save_work(&self, entity: Work) -> Result<usize, Error>;
+
+    /// Removes a customer, contract, request or work entry that has no live
+    /// records under it.
+    fn delete(&self, entity: Entity, id: u32) -> Result<usize, Error>;
+
+    /// Work counted against a contract up to `date`, with running totals.
+    /// Only requests dated within the contract period count.
+    fn usage(&self, contract_id: u32, date: NaiveDate) -> Result<ContractUsage, Error>;
+
+    /// Runs `f` as one unit: what it saves is kept only if it succeeds and this
+    /// is not a `dry_run`, so batches can be previewed and are all-or-nothing.
+    fn batch(&self, dry_run: bool, f: &mut dyn FnMut() -> Result<(), Error>) -> Result<(), Error>;
+
+    /// Writes pending changes to wherever the store keeps them, for stores
+    /// that do not save as they go.
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn add_note(&self, _request_id: u32, _author: &str, _body: &str) -> Result<usize, Error> {
+        Err(Error::Unsupported("notes".to_string()))
+    }
+
+    fn list_notes(&self, _request_id: u32) -> Result<Vec<RequestNote>, Error> {
+        Err(Error::Unsupported("notes".to_string()))
+    }
+
+    fn add_attachment(
+        &self,
+        _request_id: u32,
+        _work_id: Option<u32>,
+        _filename: &str,
+        _data: &[u8],
+    ) -> Result<usize, Error> {
+        Err(Error::Unsupported("attachments".to_string()))
+    }
+
+    fn list_attachments(&self, _request_id: u32) -> Result<Vec<Attachment>, Error> {
+        Err(Error::Unsupported("attachments".to_string()))
+    }
+
+    fn get_attachment(&self, _id: u32) -> Result<Attachment, Error> {
+        Err(Error::Unsupported("attachments".to_string()))
+    }
+
+    fn attachment_data(&self, _id: u32) -> Result<Vec<u8>, Error> {
+        Err(Error::Unsupported("attachments".to_string()))
+    }
+
+    fn history(&self, _entity: Entity, _id: u32) -> Result<Vec<AuditEntry>, Error> {
+        Err(Error::Unsupported("history".to_string()))
+    }
+
+    fn undo(&self, _steps: u32) -> Result<Vec<AuditEntry>, Error> {
+        Err(Error::Unsupported("undo".to_string()))
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashEntry>, Error> {
+        Err(Error::Unsupported("the trash".to_string()))
+    }
+
+    fn restore(&self, _entity: Entity, _id: u32) -> Result<usize, Error> {
+        Err(Error::Unsupported("the trash".to_string()))
+    }
+
+    fn purge(&self, _deleted_before: DateTime<Utc>) -> Result<Vec<(Entity, u32)>, Error> {
+        Err(Error::Unsupported("the trash".to_string()))
+    }
+
+    fn search(&self, _query: &str, _limit: u32) -> Result<Vec<SearchHit>, Error> {
+        Err(Error::Unsupported("search".to_string()))
+    }
+
+    fn import(
+        &self,
+        _export: &Export,
+        _merge: bool,
+        _overlap: Overlap,
+    ) -> Result<ImportSummary, Error> {
+        Err(Error::Unsupported("import".to_string()))
+    }
+
+    /// Saves a copy of the store under `dir`, keeping only the newest `keep`
+    /// copies there. Returns the path of the new copy.
+    fn backup(&self, _dir: Option<&Path>, _keep: usize) -> Result<PathBuf, Error> {
+        Err(Error::Unsupported("backups".to_string()))
+    }
+
+    /// Replaces everything in the store with the backup at `file`. Returns
+    /// where the store as it was has been backed up to.
+    fn restore_backup(&self, _file: &Path) -> Result<PathBuf, Error> {
+        Err(Error::Unsupported("backups".to_string()))
+    }
+
+    /// The contract of a customer that a request made on `date` draws from: the
+    /// one whose period covers `date` and that still has points left.
+    fn active_contract(&self, customer_id: u32, date: NaiveDate) -> Result<Contract, Error> {
+        // Work logged after the contract ended still counts against it.
+        let forever = NaiveDate::from_ymd_opt(9999, 12, 31).unwrap();
+        let mut contracts = Vec::new();
+        for contract in self.list_contract()? {
+            if contract.customer_id != customer_id
+                || date < contract.start_date
+                || date > contract.end_date
+            {
+                continue;
+            }
+            let summary = ContractSummary::from(&self.usage(contract.id, forever)?);
+            if summary.points_used < contract.total_points {
+                contracts.push(contract);
+            }
+        }
+
+        match contracts.len() {
+            0 => Err(Error::NoActiveContract { customer_id, date }),
+            1 => Ok(contracts.remove(0)),
+            _ => Err(Error::AmbiguousContract {
+                customer_id,
+                date,
+                contract_ids: contracts.iter().map(|c| c.id).collect(),
+            }),
+        }
+    }
+
+    /// The contract, if `date` falls outside its period. Requests dated outside
+    /// their contract's period are not counted by `usage`.
+    fn outside_contract_period(
+        &self,
+        contract_id: u32,
+        date: NaiveDate,
+    ) -> Result<Option<Contract>, Error> {
+        let contract = self.get_contract(contract_id)?;
+        if (contract.start_date..=contract.end_date).contains(&date) {
+            return Ok(None);
+        }
+        Ok(Some(contract))
+    }
+
+    /// Work that no contract's usage counts, because its request is dated
+    /// outside the period of the contract it belongs to.
+    fn unaccounted_work(&self) -> Result<Vec<UnaccountedWork>, Error> {
+        let requests: HashMap<u32, Request> = self
+            .list_request()?
+            .into_iter()
+            .map(|r| (r.id, r))
+            .collect();
+        let contracts: HashMap<u32, Contract> = self
+            .list_contract()?
+            .into_iter()
+            .map(|c| (c.id, c))
+            .collect();
+
+        let mut results = Vec::new();
+        for work in self.list_work()? {
+            let Some(request) = requests.get(&work.request_id) else {
+                continue;
+            };
+            let Some(contract) = contracts.get(&request.contract_id) else {
+                continue;
+            };
+            if (contract.start_date..=contract.end_date).contains(&request.request_date) {
+                continue;
+            }
+            results.push(UnaccountedWork {
+                work_id: work.id,
+                request_id: request.id,
+                contract_id: contract.id,
+                customer: self.get_customer(contract.customer_id).ok().map(|c| c.name),
+                contract_start_date: contract.start_date,
+                contract_end_date: contract.end_date,
+                request_date: request.request_date,
+                work_date: work.work_date,
+                worker: work.worker,
+                description: work.description,
+                points_used: work.points_used,
+            });
+        }
+        results.sort_by_key(|w| (w.contract_id, w.request_date, w.work_date));
+
+        Ok(results)
+    }
+
+    /// Pairs of contracts of the same customer whose periods overlap.
+    fn overlapping_contracts(&self) -> Result<Vec<ContractOverlap>, Error> {
+        let mut contracts = self.list_contract()?;
+        contracts.sort_by_key(|c| (c.customer_id, c.id));
+
+        let mut overlaps = Vec::new();
+        for (i, a) in contracts.iter().enumerate() {
+            for b in &contracts[i + 1..] {
+                if a.customer_id != b.customer_id
+                    || a.start_date > b.end_date
+                    || b.start_date > a.end_date
+                {
+                    continue;
+                }
+                overlaps.push(ContractOverlap {
+                    customer_id: a.customer_id,
+                    customer: self.get_customer(a.customer_id).ok().map(|c| c.name),
+                    first_contract_id: a.id,
+                    second_contract_id: b.id,
+                    from: a.start_date.max(b.start_date),
+                    to: a.end_date.min(b.end_date),
+                });
+            }
+        }
+
+        Ok(overlaps)
+    }
+
+    /// A contract's customer, terms, usage up to `date` and its requests with
+    /// the work logged on them by then.
+    fn contract_report(&self, contract_id: u32, date: NaiveDate) -> Result<ContractReport, Error> {
+        let contract = self.get_contract(contract_id)?;
+        let customer = self.get_customer(contract.customer_id)?;
+        let usage = self.usage(contract_id, date)?;
+
+        let mut requests: Vec<Request> = self
+            .list_request()?
+            .into_iter()
+            .filter(|r| r.contract_id == contract_id)
+            .collect();
+        requests.sort_by_key(|r| (r.request_date, r.id));
+        let mut all_work = self.list_work()?;
+        all_work.sort_by_key(|w| (w.work_date, w.id));
+
+        let mut reports = Vec::new();
+        for request in requests {
+            let work: Vec<Work> = all_work
+                .iter()
+                .filter(|w| w.request_id == request.id && w.work_date <= date)
+                .cloned()
+                .collect();
+            let status = if request.request_date < contract.start_date
+                || request.request_date > contract.end_date
+            {
+                RequestStatus::Unaccounted
+            } else if work.is_empty() {
+                RequestStatus::Open
+            } else {
+                RequestStatus::Worked
+            };
+            reports.push(RequestReport {
+                id: request.id,
+                request_date: request.request_date,
+                description: request.description,
+                status,
+                points_used: work.iter().map(|w| w.points_used).sum(),
+                work,
+            });
+        }
+
+        Ok(ContractReport {
+            date,
+            customer_id: customer.id,
+            customer: customer.name,
+            contract: ContractSummary::from(&usage),
+            requests: reports,
+            usage,
+        })
+    }
+
+    /// Usage of a contract from `from` to `to`, inclusive, with the points
+    /// already used before `from` as the opening balance.
+    fn usage_between(
+        &self,
+        contract_id: u32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<UsageStatement, Error> {
+        if from > to {
+            return Err(Error::Validation(vec![FieldError::new(
+                "from",
+                "must not be after to",
+            )]));
+        }
+
+        let usage = self.usage(contract_id, to)?;
+        let (before, within): (Vec<_>, Vec<_>) = usage
+            .cumulative_usage
+            .into_iter()
+            .partition(|u| u.work_date < from);
+        let opening_points_used: u32 = before.iter().map(|u| u.points_used).sum();
+
+        let mut cumulative = opening_points_used;
+        let mut cumulative_usage = Vec::new();
+        for mut u in within {
+            cumulative += u.points_used;
+            u.cumulative_points_used = cumulative;
+            cumulative_usage.push(u);
+        }
+
+        Ok(UsageStatement {
+            contract_id: usage.contract_id,
+            start_date: usage.start_date,
+            end_date: usage.end_date,
+            total_points: usage.total_points,
+            from,
+            to,
+            opening_points_used,
+            opening_balance: usage.total_points as i64 - opening_points_used as i64,
+            points_used: cumulative - opening_points_used,
+            closing_balance: usage.total_points as i64 - cumulative as i64,
+            cumulative_usage,
+        })
+    }
+
+    /// Usage of every contract of a customer up to `date`, oldest contract
+    /// first, using the same rules as `usage`.
+    fn customer_usage(&self, customer_id: u32, date: NaiveDate) -> Result<CustomerUsage, Error> {
+        let customer = self.get_customer(customer_id)?;
+
+        let mut contract_list: Vec<Contract> = self
+            .list_contract()?
+            .into_iter()
+            .filter(|c| c.customer_id == customer_id)
+            .collect();
+        contract_list.sort_by_key(|c| (c.start_date, c.id));
+        let mut contracts = Vec::new();
+        for contract in contract_list {
+            contracts.push(ContractSummary::from(&self.usage(contract.id, date)?));
+        }
+
+        let total_points = contracts.iter().map(|c| c.total_points).sum();
+        let points_used = contracts.iter().map(|c| c.points_used).sum();
+        Ok(CustomerUsage {
+            customer_id,
+            customer: customer.name,
+            contracts,
+            total_points,
+            points_used,
+            points_remaining: total_points as i64 - points_used as i64,
+        })
+    }
+
+    /// Every contract in force on `date`, most at risk of running out of points
+    /// first.
+    fn dashboard(&self, date: NaiveDate) -> Result<Vec<DashboardEntry>, Error> {
+        let mut entries = Vec::new();
+        for contract in self.list_contract()? {
+            if date < contract.start_date || date > contract.end_date {
+                continue;
+            }
+            let customer = self.get_customer(contract.customer_id).ok().map(|c| c.name);
+            let summary = ContractSummary::from(&self.usage(contract.id, date)?);
+            let period_days = (contract.end_date - contract.start_date).num_days() + 1;
+            let elapsed_days = (date - contract.start_date).num_days() + 1;
+            let burn_percent = if contract.total_points == 0 {
+                100.0
+            } else {
+                summary.points_used as f64 * 100.0 / contract.total_points as f64
+            };
+            let elapsed_percent = elapsed_days as f64 * 100.0 / period_days as f64;
+            entries.push(DashboardEntry {
+                contract_id: contract.id,
+                customer_id: contract.customer_id,
+                customer,
+                start_date: contract.start_date,
+                end_date: contract.end_date,
+                days_remaining: (contract.end_date - date).num_days(),
+                total_points: contract.total_points,
+                points_used: summary.points_used,
+                burn_percent,
+                elapsed_percent,
+                status: BurnStatus::new(burn_percent, elapsed_percent),
+            });
+        }
+
+        entries.sort_by(|a, b| {
+            b.status
+                .cmp(&a.status)
+                .then(
+                    (b.burn_percent - b.elapsed_percent)
+                        .total_cmp(&(a.burn_percent - a.elapsed_percent)),
+                )
+                .then(a.days_remaining.cmp(&b.days_remaining))
+        });
+
+        Ok(entries)
+    }
+
+    /// All live customers, contracts, requests and work, ids included.
+    fn export(&self) -> Result<Export, Error> {
+        Ok(Export {
+            version: EXPORT_VERSION,
+            customers: self
+                .list_customer()?
+                .into_iter()
+                .map(|c| ExportedCustomer {
+                    id: c.id,
+                    name: c.name,
+                })
+                .collect(),
+            contracts: self
+                .list_contract()?
+                .into_iter()
+                .map(|c| ExportedContract {
+                    id: c.id,
+                    customer_id: c.customer_id,
+                    start_date: c.start_date,
+                    end_date: c.end_date,
+                    total_points: c.total_points,
+                })
+                .collect(),
+            requests: self
+                .list_request()?
+                .into_iter()
+                .map(|r| ExportedRequest {
+                    id: r.id,
+                    contract_id: r.contract_id,
+                    description: r.description,
+                    request_date: r.request_date,
+                })
+                .collect(),
+            work: self
+                .list_work()?
+                .into_iter()
+                .map(|w| ExportedWork {
+                    id: w.id,
+                    request_id: w.request_id,
+                    worker: w.worker,
+                    description: w.description,
+                    points_used: w.points_used,
+                    work_date: w.work_date,
+                })
+                .collect(),
+        })
+    }
+}